@@ -1,5 +1,8 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use vpk::vpk::ProbableKind;
+use vpk::access::DirFile;
+use vpk::entry::{VPKDirectoryEntry, VPKEntry};
+use vpk::structs::VPKHeader;
+use vpk::vpk::{Ext, ProbableKind, ReadOptions};
 
 fn bench_vpk_read(c: &mut Criterion) {
     let file_path = std::env::var("VPK_FILE")
@@ -23,5 +26,133 @@ fn bench_vpk_read(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_vpk_read);
+/// Rough size of the maps' storage for the entries, ignoring the unused capacity
+fn entries_size(vpk: &vpk::VPK) -> usize {
+    vpk.entries().count() * (std::mem::size_of::<DirFile>() + std::mem::size_of::<VPKEntry>())
+}
+
+fn bench_vpk_read_filtered(c: &mut Criterion) {
+    let file_path = std::env::var("VPK_FILE")
+        .expect("Please set VPK_FILE env var to the VPK file to benchmark");
+    let file_path = std::path::Path::new(&file_path);
+
+    let options = ReadOptions {
+        ext_filter: Some([Ext::Mdl, Ext::Vtx, Ext::Vvd, Ext::Phy].into()),
+        ..ReadOptions::default()
+    };
+
+    // Criterion only measures time, so report the memory difference once up front
+    let full = vpk::VPK::read_with_options(file_path, &ReadOptions::default()).unwrap();
+    let filtered = vpk::VPK::read_with_options(file_path, &options).unwrap();
+    eprintln!(
+        "all entries: {} ({} bytes), model entries: {} ({} bytes)",
+        full.entries().count(),
+        entries_size(&full),
+        filtered.entries().count(),
+        entries_size(&filtered),
+    );
+
+    let mut group = c.benchmark_group("ext-filter");
+    group.bench_function("all", |b| {
+        b.iter(|| {
+            let res = vpk::VPK::read_with_options(file_path, &ReadOptions::default()).unwrap();
+
+            let _res = black_box(res);
+        });
+    });
+    group.bench_function("models", |b| {
+        b.iter(|| {
+            let res = vpk::VPK::read_with_options(file_path, &options).unwrap();
+
+            let _res = black_box(res);
+        });
+    });
+    group.finish();
+}
+
+/// A version 1 dir file with `entries_per_archive` vtf entries in each of `archives` archives,
+/// like the dir files of large games which have hundreds of archives
+fn many_archives_vpk(archives: u16, entries_per_archive: u32) -> Vec<u8> {
+    let mut tree = b"vtf\0".to_vec();
+    for archive_index in 0..archives {
+        tree.extend_from_slice(format!("materials/{archive_index}\0").as_bytes());
+        for i in 0..entries_per_archive {
+            tree.extend_from_slice(format!("texture{i}\0").as_bytes());
+            let dir_entry = VPKDirectoryEntry {
+                crc32: 0,
+                preload_length: 0,
+                archive_index,
+                archive_offset: i * 16,
+                file_length: 16,
+                suffix: 0xffff,
+            };
+            dir_entry.write_le(&mut tree).unwrap();
+        }
+        tree.push(0);
+    }
+    tree.extend_from_slice(b"\0\0");
+
+    let header = VPKHeader {
+        signature: 0x55aa1234,
+        version: 1,
+        tree_length: tree.len() as u32,
+    };
+    let mut data = Vec::new();
+    header.write_le(&mut data).unwrap();
+    data.extend_from_slice(&tree);
+    data
+}
+
+fn bench_vpk_read_many_archives(c: &mut Criterion) {
+    let data: std::sync::Arc<[u8]> = many_archives_vpk(500, 20).into();
+
+    c.bench_function("many-archives", |b| {
+        b.iter(|| {
+            let res = vpk::VPK::read_bytes(
+                data.clone(),
+                |i| format!("pak01_{i:03}.vpk"),
+                ProbableKind::None,
+            )
+            .unwrap();
+
+            let _res = black_box(res);
+        });
+    });
+}
+
+fn bench_vpk_read_intern_dirs(c: &mut Criterion) {
+    let file_path = std::env::var("VPK_FILE")
+        .expect("Please set VPK_FILE env var to the VPK file to benchmark");
+    let file_path = std::path::Path::new(&file_path);
+
+    let options = ReadOptions {
+        intern_dirs: true,
+        ..ReadOptions::default()
+    };
+
+    let mut group = c.benchmark_group("intern-dirs");
+    group.bench_function("off", |b| {
+        b.iter(|| {
+            let res = vpk::VPK::read_with_options(file_path, &ReadOptions::default()).unwrap();
+
+            let _res = black_box(res);
+        });
+    });
+    group.bench_function("on", |b| {
+        b.iter(|| {
+            let res = vpk::VPK::read_with_options(file_path, &options).unwrap();
+
+            let _res = black_box(res);
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_vpk_read_many_archives,
+    bench_vpk_read,
+    bench_vpk_read_filtered,
+    bench_vpk_read_intern_dirs
+);
 criterion_main!(benches);
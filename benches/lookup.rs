@@ -0,0 +1,101 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use vpk::access::DirFile;
+use vpk::entry::VPKEntry;
+use vpk::vpk::{Ext, ReadOptions};
+use vpk::VPK;
+
+/// The most paths to look up per iteration, so that huge VPKs don't make each iteration slow
+const MAX_PATHS: usize = 10_000;
+
+/// A sample of the entries of the VPK as `(ext, dir, filename)`, lowercased
+fn lookup_paths(vpk: &VPK) -> Vec<(Ext<'static>, String, String)> {
+    let count = vpk.entries().count();
+    let step = count.div_ceil(MAX_PATHS).max(1);
+    vpk.iter()
+        .step_by(step)
+        .map(|handle| {
+            let dir = String::from_utf8_lossy(handle.dir_file.dir())
+                .to_ascii_lowercase()
+                .replace('\\', "/");
+            let filename = String::from_utf8_lossy(handle.dir_file.filename()).to_ascii_lowercase();
+            (handle.ext.into_owned(), dir, filename)
+        })
+        .collect()
+}
+
+fn bench_vpk_get(c: &mut Criterion) {
+    let file_path = std::env::var("VPK_FILE")
+        .expect("Please set VPK_FILE env var to the VPK file to benchmark");
+    let file_path = std::path::Path::new(&file_path);
+
+    let vpk = VPK::read_with_options(file_path, &ReadOptions::default()).unwrap();
+    let normalized = VPK::read_with_options(
+        file_path,
+        &ReadOptions {
+            normalize_case: true,
+            ..ReadOptions::default()
+        },
+    )
+    .unwrap();
+    let paths = lookup_paths(&vpk);
+
+    let mut group = c.benchmark_group("lookup");
+    // Reports lookups per second
+    group.throughput(Throughput::Elements(paths.len() as u64));
+
+    group.bench_function("get", |b| {
+        b.iter(|| {
+            for (ext, dir, filename) in &paths {
+                black_box(vpk.get(ext, dir, filename));
+            }
+        });
+    });
+
+    group.bench_function("get-normalized", |b| {
+        b.iter(|| {
+            for (ext, dir, filename) in &paths {
+                black_box(normalized.get(ext, dir, filename));
+            }
+        });
+    });
+
+    group.bench_function("get_ignore_case", |b| {
+        b.iter(|| {
+            for (ext, dir, filename) in &paths {
+                black_box(vpk.get_ignore_case(ext, dir, filename));
+            }
+        });
+    });
+
+    group.bench_function("getf", |b| {
+        let tree = vpk.tree();
+        b.iter(|| {
+            for (ext, dir, filename) in &paths {
+                black_box(tree.getf(ext, dir, filename));
+            }
+        });
+    });
+
+    // Binary search instead of hashing. It ignores case like `get_ignore_case`, but the paths
+    // are lowercase so `get` and `getf` find the same entries.
+    let sorted = vpk.sorted_tree();
+    // The sorted tree is a copy of the entries, so report its memory once up front
+    let entry_size = std::mem::size_of::<(Ext<'static>, DirFile, VPKEntry)>();
+    eprintln!(
+        "sorted tree: {} entries, {} bytes on top of the maps",
+        sorted.len(),
+        sorted.len() * entry_size,
+    );
+    group.bench_function("sorted", |b| {
+        b.iter(|| {
+            for (ext, dir, filename) in &paths {
+                black_box(sorted.get(ext, dir, filename));
+            }
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_vpk_get);
+criterion_main!(benches);
@@ -0,0 +1,88 @@
+//! Read a VPK that is inside a zip file, without extracting it.
+//!
+//! Usage: `zip <path to zip> <path of the dir vpk in the zip>`, like
+//! `zip game.zip tf/tf2_misc_dir.vpk`. The archive files are expected to be next to the dir file
+//! in the zip.
+
+use std::cell::{OnceCell, RefCell};
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
+
+use vpk::entry::VpkReaderProvider;
+use vpk::vpk::ProbableKind;
+use vpk::VPK;
+use zip::ZipArchive;
+
+/// Reads each archive file out of the zip the first time it is needed, and keeps it in memory.
+/// Compressed zip members can't be seeked, so the whole archive file has to be read.
+struct ZipArchives<'v, R> {
+    zip: RefCell<ZipArchive<R>>,
+    /// The name of each archive file in the zip, indexed by archive index
+    names: &'v [Option<std::sync::Arc<str>>],
+    archives: Vec<OnceCell<Vec<u8>>>,
+}
+impl<'v, R: Read + Seek> ZipArchives<'v, R> {
+    fn new(zip: ZipArchive<R>, vpk: &'v VPK) -> Self {
+        ZipArchives {
+            zip: RefCell::new(zip),
+            names: &vpk.archive_paths,
+            archives: vpk.archive_paths.iter().map(|_| OnceCell::new()).collect(),
+        }
+    }
+}
+impl<R: Read + Seek> VpkReaderProvider for ZipArchives<'_, R> {
+    type Reader<'a>
+        = Cursor<&'a [u8]>
+    where
+        Self: 'a;
+
+    fn vpk_reader(&self, archive_index: u16) -> std::io::Result<Option<Self::Reader<'_>>> {
+        let index = usize::from(archive_index);
+        let (Some(cell), Some(Some(name))) = (self.archives.get(index), self.names.get(index))
+        else {
+            return Err(std::io::ErrorKind::NotFound.into());
+        };
+
+        if cell.get().is_none() {
+            let mut zip = self.zip.borrow_mut();
+            let mut file = zip.by_name(name)?;
+            let mut data = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut data)?;
+            // Can't already be set, since we're the only one borrowing the zip
+            let _ = cell.set(data);
+        }
+
+        Ok(cell.get().map(|data| Cursor::new(data.as_slice())))
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<_> = std::env::args().collect();
+    let [_, zip_path, dir_name] = args.as_slice() else {
+        return Err("Usage: zip <path to zip> <path of the dir vpk in the zip>".into());
+    };
+
+    let mut zip = ZipArchive::new(File::open(zip_path)?)?;
+
+    let mut dir_data = Vec::new();
+    zip.by_name(dir_name)?.read_to_end(&mut dir_data)?;
+
+    // Name the archive files like `pak01_007.vpk` for `pak01_dir.vpk`, in the same folder of the
+    // zip, which is what the provider looks them up by
+    let prefix = dir_name
+        .strip_suffix("dir.vpk")
+        .ok_or("The dir vpk should end with `dir.vpk`")?;
+    let vpk = VPK::read_bytes(
+        dir_data,
+        |i| format!("{prefix}{i:03}.vpk"),
+        ProbableKind::None,
+    )?;
+
+    let archives = ZipArchives::new(zip, &vpk);
+    for handle in vpk.iter() {
+        let data = handle.get_with_files(&archives)?;
+        println!("{} ({} bytes)", handle.full_path(), data.len());
+    }
+
+    Ok(())
+}
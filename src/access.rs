@@ -8,33 +8,96 @@ use indexmap::{Equivalent, IndexMap};
 
 use crate::entry::VPKEntry;
 
-fn hash_bytes<H: Hasher>(state: &mut H, bytes: &[u8]) {
+/// Hash the bytes as they are compared by [`path_eq_ignore_case`]
+fn hash_path<H: Hasher>(state: &mut H, bytes: &[u8]) {
     // We can't trust that the hash implementation doesn't do a slice of bytes differently from
-    // writing bytes individually, and we need to write some bytes individually in some of the hash
-    //impls
+    // writing bytes individually, and the dirs of the big refs are hashed in pieces
     for v in bytes {
-        state.write_u8(*v);
+        state.write_u8(normalize_path_byte(*v));
     }
 }
 
-fn hash_bytes_as_lowercase<H: Hasher>(state: &mut H, bytes: &[u8]) {
+fn hash_filename<H: Hasher>(state: &mut H, bytes: &[u8]) {
     for v in bytes {
         state.write_u8(v.to_ascii_lowercase());
     }
+    state.write_u8(0xff);
 }
 
-fn hash_str<H: Hasher>(state: &mut H, s: &str) {
-    hash_bytes(state, s.as_bytes());
-    state.write_u8(0xff);
+/// Compare two paths, ignoring ascii case and treating `\` the same as `/`, since some VPKs are
+/// written with `\` as the separator.
+pub(crate) fn path_eq_ignore_case(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| normalize_path_byte(*a) == normalize_path_byte(*b))
+}
+
+/// Lowercase the byte, and turn `\\` into `/`
+pub(crate) fn normalize_path_byte(v: u8) -> u8 {
+    if v == b'\\' {
+        b'/'
+    } else {
+        v.to_ascii_lowercase()
+    }
+}
+
+/// Strip a leading separator, either `/` or `\`
+fn strip_separator(path: &[u8]) -> Option<&[u8]> {
+    path.strip_prefix(b"/").or_else(|| path.strip_prefix(b"\\"))
 }
 
-fn hash_str_as_lowercase<H: Hasher>(state: &mut H, s: &str) {
-    hash_bytes_as_lowercase(state, s.as_bytes());
+/// Whether a separator goes between `dir` and the rest of a big ref's dir
+fn needs_separator(dir: &[u8]) -> bool {
+    !dir.is_empty() && !dir.ends_with(b"/") && !dir.ends_with(b"\\")
+}
+
+/// Hash the dir of a big ref like the dir it is equivalent to, see [`big_dir_eq`]
+fn hash_big_dir<H: Hasher>(state: &mut H, dir: &[u8], extra_dir: &[u8]) {
+    hash_path(state, dir);
+    if !extra_dir.is_empty() {
+        if needs_separator(dir) {
+            state.write_u8(b'/');
+        }
+        hash_path(state, extra_dir);
+    }
     state.write_u8(0xff);
 }
 
+/// Whether `key_dir` is `dir` joined with `extra_dir`, with a separator between them unless
+/// `dir` is empty or already ends with one
+fn big_dir_eq(
+    key_dir: &[u8],
+    dir: &[u8],
+    extra_dir: &[u8],
+    eq: impl Fn(&[u8], &[u8]) -> bool,
+) -> bool {
+    if extra_dir.is_empty() {
+        return eq(key_dir, dir);
+    }
+
+    let Some(start_dir) = key_dir.get(..dir.len()) else {
+        return false;
+    };
+    if !eq(start_dir, dir) {
+        return false;
+    }
+
+    let rem_dir = &key_dir[dir.len()..];
+    let rem_dir = if needs_separator(dir) {
+        match strip_separator(rem_dir) {
+            Some(rem_dir) => rem_dir,
+            None => return false,
+        }
+    } else {
+        rem_dir
+    };
+    eq(rem_dir, extra_dir)
+}
+
 /// A reference to a specific (dir, filename), without the extension.  
-/// This should be lowercase!
+/// The dir and filename are kept exactly as they are stored in the VPK, but they are compared
+/// case-insensitively, and with `\` in the dir treated as `/`.
 #[derive(Clone)]
 pub struct DirFile {
     /// A copy of the data, this lets us avoid keeping a copy of `dir` or `filename`
@@ -55,6 +118,23 @@ impl DirFile {
         &self.data[self.dir.clone()]
     }
 
+    /// Like [`DirFile::dir`], but empty for entries in the root rather than the single space that
+    /// VPKs store for them.
+    pub fn dir_or_empty(&self) -> &[u8] {
+        match self.dir() {
+            b" " => b"",
+            dir => dir,
+        }
+    }
+
+    /// The range of the dir in the VPK's data.  
+    /// Entries in the same dir with the same extension share the same range, and with
+    /// [`ReadOptions::intern_dirs`](crate::vpk::ReadOptions::intern_dirs) so do entries with
+    /// different extensions.
+    pub fn dir_range(&self) -> Range<usize> {
+        self.dir.clone()
+    }
+
     pub fn filename(&self) -> &[u8] {
         &self.data[self.filename.clone()]
     }
@@ -64,15 +144,14 @@ impl DirFile {
 // hash for str is not decided.
 impl Hash for DirFile {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        // hash_bytes_as_lowercase(state, self.dir());
-        // state.write_u8(0xff);
-        hash_bytes_as_lowercase(state, self.filename());
+        hash_path(state, self.dir());
         state.write_u8(0xff);
+        hash_filename(state, self.filename());
     }
 }
 impl PartialEq for DirFile {
     fn eq(&self, other: &Self) -> bool {
-        self.dir().eq_ignore_ascii_case(other.dir())
+        path_eq_ignore_case(self.dir(), other.dir())
             && self.filename().eq_ignore_ascii_case(other.filename())
     }
 }
@@ -100,7 +179,7 @@ impl<'a> DirFileRef<'a> {
 }
 impl Equivalent<DirFile> for DirFileRef<'_> {
     fn equivalent(&self, key: &DirFile) -> bool {
-        self.dir.as_bytes().eq_ignore_ascii_case(key.dir())
+        path_eq_ignore_case(self.dir.as_bytes(), key.dir())
             && self
                 .filename
                 .as_bytes()
@@ -109,8 +188,9 @@ impl Equivalent<DirFile> for DirFileRef<'_> {
 }
 impl Hash for DirFileRef<'_> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        // hash_str(state, self.dir);
-        hash_str(state, self.filename);
+        hash_path(state, self.dir.as_bytes());
+        state.write_u8(0xff);
+        hash_filename(state, self.filename.as_bytes());
     }
 }
 
@@ -128,7 +208,7 @@ impl<'a> DirFileRefLowercase<'a> {
 }
 impl Equivalent<DirFile> for DirFileRefLowercase<'_> {
     fn equivalent(&self, key: &DirFile) -> bool {
-        self.dir.as_bytes().eq_ignore_ascii_case(key.dir())
+        path_eq_ignore_case(self.dir.as_bytes(), key.dir())
             && self
                 .filename
                 .as_bytes()
@@ -137,8 +217,9 @@ impl Equivalent<DirFile> for DirFileRefLowercase<'_> {
 }
 impl Hash for DirFileRefLowercase<'_> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        // hash_str_as_lowercase(state, self.dir);
-        hash_str_as_lowercase(state, self.filename);
+        hash_path(state, self.dir.as_bytes());
+        state.write_u8(0xff);
+        hash_filename(state, self.filename.as_bytes());
     }
 }
 
@@ -147,15 +228,15 @@ impl Hash for DirFileRefLowercase<'_> {
 /// The filename is potentially 'big', and is broken apart if needed
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct DirFileBigRef<'a> {
-    /// Should *not* end with a '/'
+    /// Should *not* end with a '/' or '\\'
     pub dir: &'a str,
-    /// Should *not* start with a '/'
+    /// Should *not* start with a '/' or '\\'
     pub extra_dir: &'a str,
     pub filename: &'a str,
 }
 impl<'a> DirFileBigRef<'a> {
     pub fn new(dir: &'a str, big_filename: &'a str) -> DirFileBigRef<'a> {
-        let res = big_filename.rsplit_once('/');
+        let res = big_filename.rsplit_once(['/', '\\']);
         let (extra_dir, filename) = match res {
             Some(v) => v,
             None => ("", big_filename),
@@ -171,65 +252,77 @@ impl<'a> DirFileBigRef<'a> {
 // TODO: write some tests for this
 impl Equivalent<DirFile> for DirFileBigRef<'_> {
     fn equivalent(&self, key: &DirFile) -> bool {
-        let dir_size = self.dir.len();
-        let total_size = dir_size + self.extra_dir.len();
-        if total_size > key.dir.len() {
-            return false;
-        }
+        self.filename
+            .as_bytes()
+            .eq_ignore_ascii_case(key.filename())
+            && big_dir_eq(
+                key.dir(),
+                self.dir.as_bytes(),
+                self.extra_dir.as_bytes(),
+                path_eq_ignore_case,
+            )
+    }
+}
+impl Hash for DirFileBigRef<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_big_dir(state, self.dir.as_bytes(), self.extra_dir.as_bytes());
+        hash_filename(state, self.filename.as_bytes());
+    }
+}
 
-        let key_dir = key.dir();
-        let start_dir = &key_dir[..dir_size];
-        if !start_dir.eq_ignore_ascii_case(self.dir.as_bytes()) {
-            return false;
-        }
+/// Like [`DirFileBigRef`], but compares the bytes exactly rather than ignoring case.  
+/// This is only correct for VPKs read with
+/// [`ReadOptions::normalize_case`](crate::vpk::ReadOptions::normalize_case), where the keys are
+/// already lowercase with `/` separators, and so the dir and filename should be too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirFileBigRefExact<'a> {
+    /// Should *not* end with a '/'
+    pub dir: &'a str,
+    /// Should *not* start with a '/'
+    pub extra_dir: &'a str,
+    pub filename: &'a str,
+}
+impl<'a> DirFileBigRefExact<'a> {
+    pub fn new(dir: &'a str, big_filename: &'a str) -> DirFileBigRefExact<'a> {
+        let (extra_dir, filename) = big_filename.rsplit_once('/').unwrap_or(("", big_filename));
 
-        let rem_dir = key_dir.get(dir_size..).unwrap_or(b"");
-        if self.extra_dir.is_empty() {
-            rem_dir.is_empty()
-                && self
-                    .filename
-                    .as_bytes()
-                    .eq_ignore_ascii_case(key.filename())
-        } else if let Some(rem_dir) = rem_dir.strip_prefix(b"/") {
-            rem_dir.eq_ignore_ascii_case(self.extra_dir.as_bytes())
-                && self
-                    .filename
-                    .as_bytes()
-                    .eq_ignore_ascii_case(key.filename())
-        } else {
-            rem_dir.eq_ignore_ascii_case(self.extra_dir.as_bytes())
-                && self
-                    .filename
-                    .as_bytes()
-                    .eq_ignore_ascii_case(key.filename())
+        DirFileBigRefExact {
+            dir,
+            extra_dir,
+            filename,
         }
     }
 }
-impl Hash for DirFileBigRef<'_> {
+impl Equivalent<DirFile> for DirFileBigRefExact<'_> {
+    fn equivalent(&self, key: &DirFile) -> bool {
+        self.filename.as_bytes() == key.filename()
+            && big_dir_eq(
+                key.dir(),
+                self.dir.as_bytes(),
+                self.extra_dir.as_bytes(),
+                |a, b| a == b,
+            )
+    }
+}
+// The keys are already normalized, so hashing them like the other refs gives the same hash
+impl Hash for DirFileBigRefExact<'_> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        // hash_bytes(state, self.dir.as_bytes());
-        // if !self.extra_dir.is_empty() {
-        //     if !self.dir.is_empty() {
-        //         state.write_u8(b'/');
-        //     }
-        //     hash_bytes(state, self.extra_dir.as_bytes());
-        // }
-        // state.write_u8(0xff);
-        hash_str(state, self.filename);
+        hash_big_dir(state, self.dir.as_bytes(), self.extra_dir.as_bytes());
+        hash_filename(state, self.filename.as_bytes());
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct DirFileBigRefLowercase<'a> {
-    /// Should *not* end with a '/'
+    /// Should *not* end with a '/' or '\\'
     pub dir: &'a str,
-    /// Should *not* start with a '/'
+    /// Should *not* start with a '/' or '\\'
     pub extra_dir: &'a str,
     pub filename: &'a str,
 }
 impl<'a> DirFileBigRefLowercase<'a> {
     pub fn new(dir: &'a str, big_filename: &'a str) -> DirFileBigRefLowercase<'a> {
-        let res = big_filename.rsplit_once('/');
+        let res = big_filename.rsplit_once(['/', '\\']);
         let (extra_dir, filename) = match res {
             Some(v) => v,
             None => ("", big_filename),
@@ -244,58 +337,32 @@ impl<'a> DirFileBigRefLowercase<'a> {
 }
 impl Equivalent<DirFile> for DirFileBigRefLowercase<'_> {
     fn equivalent(&self, key: &DirFile) -> bool {
-        let dir_size = self.dir.len();
-        let total_size = dir_size + self.extra_dir.len();
-        if total_size > key.dir.len() {
-            return false;
-        }
-
-        let key_dir = key.dir();
-        let start_dir = &key_dir[..dir_size];
-        if !start_dir.eq_ignore_ascii_case(self.dir.as_bytes()) {
-            return false;
-        }
-
-        let rem_dir = key_dir.get(dir_size..).unwrap_or(b"");
-        if self.extra_dir.is_empty() {
-            rem_dir.is_empty()
-                && self
-                    .filename
-                    .as_bytes()
-                    .eq_ignore_ascii_case(key.filename())
-        } else if let Some(rem_dir) = rem_dir.strip_prefix(b"/") {
-            rem_dir.eq_ignore_ascii_case(self.extra_dir.as_bytes())
-                && self
-                    .filename
-                    .as_bytes()
-                    .eq_ignore_ascii_case(key.filename())
-        } else {
-            rem_dir.eq_ignore_ascii_case(self.extra_dir.as_bytes())
-                && self
-                    .filename
-                    .as_bytes()
-                    .eq_ignore_ascii_case(key.filename())
-        }
+        self.filename
+            .as_bytes()
+            .eq_ignore_ascii_case(key.filename())
+            && big_dir_eq(
+                key.dir(),
+                self.dir.as_bytes(),
+                self.extra_dir.as_bytes(),
+                path_eq_ignore_case,
+            )
     }
 }
 impl Hash for DirFileBigRefLowercase<'_> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        // hash_bytes_as_lowercase(state, self.dir.as_bytes());
-        // if !self.extra_dir.is_empty() {
-        //     if !self.dir.is_empty() {
-        //         state.write_u8(b'/');
-        //     }
-        //     hash_bytes_as_lowercase(state, self.extra_dir.as_bytes());
-        // }
-        // state.write_u8(0xff);
-        hash_str_as_lowercase(state, self.filename);
+        hash_big_dir(state, self.dir.as_bytes(), self.extra_dir.as_bytes());
+        hash_filename(state, self.filename.as_bytes());
     }
 }
 
 pub type MapRandomState = ahash::RandomState;
+/// The standard library's SipHash, which is slower than `ahash` but resistant to collisions
+/// crafted to make lookups slow. See [`crate::vpk::ReadOptions::hash_builder`].
+pub type SipRandomState = std::collections::hash_map::RandomState;
+
 /// (Dir, Filename) -> VPKEntry
 /// This uses a tuple because you rarely need to iterate over all the entries in a directory.
-pub type DirFileEntryMap = IndexMap<DirFile, VPKEntry, MapRandomState>;
+pub type DirFileEntryMap<S = MapRandomState> = IndexMap<DirFile, VPKEntry, S>;
 
 #[cfg(test)]
 mod tests {
@@ -306,7 +373,7 @@ mod tests {
 
     use indexmap::Equivalent;
 
-    use super::{DirFile, DirFileBigRef, DirFileBigRefLowercase, DirFileRef};
+    use super::{DirFile, DirFileBigRef, DirFileBigRefExact, DirFileBigRefLowercase, DirFileRef};
 
     #[track_caller]
     fn a_eq<T: Equivalent<DirFile> + Hash + std::fmt::Debug>(a: &DirFile, b: T) {
@@ -420,4 +487,84 @@ mod tests {
         a_eq(&a, DirFileRef::new("materials/concrete", "computerwall003"));
         a_eq(&a, b);
     }
+
+    #[test]
+    fn dir_file_backslash() {
+        let data = b"models\\props;crate";
+        let data: Arc<[u8]> = Arc::from(*data);
+        let a = DirFile::new(data.clone(), 0..12, 13..data.len());
+        a_eq(&a, DirFileRef::new("models/props", "crate"));
+        a_eq(&a, DirFileBigRef::new("models", "props/crate"));
+        a_eq(&a, DirFileBigRefLowercase::new("Models", "PROPS\\crate"));
+        a_neq(&a, DirFileRef::new("models/prop", "crate"));
+
+        let b = DirFile::new(Arc::from(*b"models/props;crate"), 0..12, 13..18);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn dir_file_big_exact() {
+        let data = b"materials/concrete;concretefloor001a";
+        let data: Arc<[u8]> = Arc::from(*data);
+        let a = DirFile::new(data.clone(), 0..18, 19..data.len());
+        a_eq(
+            &a,
+            DirFileBigRefExact::new("materials", "concrete/concretefloor001a"),
+        );
+        a_eq(
+            &a,
+            DirFileBigRefExact::new("materials/concrete", "concretefloor001a"),
+        );
+        a_neq(
+            &a,
+            DirFileBigRefExact::new("materials", "concrete/concretefloor001A"),
+        );
+        a_neq(
+            &a,
+            DirFileBigRefExact::new("materials", "concretefloor001a"),
+        );
+        a_neq(
+            &a,
+            DirFileBigRefExact::new("materials/concrete", "x/concretefloor001a"),
+        );
+    }
+
+    fn hash_of(v: impl Hash) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        v.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn dir_file_hash_dir() {
+        // Same filename in different dirs shouldn't all land in the same bucket
+        let a = DirFile::new(Arc::from(*b"materials/brick;wall"), 0..15, 16..20);
+        let b = DirFile::new(Arc::from(*b"materials/metal;wall"), 0..15, 16..20);
+        assert_ne!(a, b);
+        assert_ne!(hash_of(&a), hash_of(&b));
+        assert_ne!(
+            hash_of(DirFileRef::new("materials/brick", "wall")),
+            hash_of(DirFileRef::new("materials/metal", "wall"))
+        );
+        assert_ne!(
+            hash_of(DirFileBigRef::new("materials", "brick/wall")),
+            hash_of(DirFileBigRef::new("materials", "metal/wall"))
+        );
+
+        // The split between the dir and filename is part of the key
+        let c = DirFile::new(Arc::from(*b"materials;brickwall"), 0..9, 10..19);
+        let d = DirFile::new(Arc::from(*b"materialsbrick;wall"), 0..14, 15..19);
+        assert_ne!(c, d);
+        assert_ne!(hash_of(&c), hash_of(&d));
+    }
+
+    #[test]
+    fn dir_file_dir_or_empty() {
+        let root = DirFile::new(Arc::from(*b" ;readme"), 0..1, 2..8);
+        assert_eq!(root.dir(), b" ");
+        assert_eq!(root.dir_or_empty(), b"");
+
+        let nested = DirFile::new(Arc::from(*b"materials;brick"), 0..9, 10..15);
+        assert_eq!(nested.dir_or_empty(), b"materials");
+    }
 }
@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Error;
+use std::sync::Arc;
+
+use crate::entry::{VPKEntryHandle, VpkReaderProvider};
+
+/// The dir file data of the VPK, and the start of the entry's preload data, which is unique to
+/// the entry within the VPK
+type CacheKey = (usize, usize);
+
+/// A cache of the data of entries, for when the same entries are read over and over, like icons
+/// or common materials. Once the cached data is over the byte budget, the least recently used
+/// entries are evicted.  
+/// This is separate from [`crate::VPK`] so that caching is opt-in. It can hold entries from
+/// multiple VPKs, and keeps the dir file data of each alive while it has entries from it.
+#[derive(Debug)]
+pub struct VpkCache {
+    max_bytes: usize,
+    total_bytes: usize,
+    /// Incremented on every access, to order the entries by when they were last used
+    tick: u64,
+    entries: HashMap<CacheKey, CachedEntry>,
+    /// Last used tick -> key, the first is the least recently used
+    order: BTreeMap<u64, CacheKey>,
+}
+
+#[derive(Debug)]
+struct CachedEntry {
+    data: Arc<[u8]>,
+    /// Keeps the dir file data alive, so that its address can't be reused by another VPK while
+    /// it is part of a key
+    _vpk_data: Arc<[u8]>,
+    last_used: u64,
+}
+
+impl VpkCache {
+    /// Create a cache that holds at most `max_bytes` of entry data
+    pub fn new(max_bytes: usize) -> VpkCache {
+        VpkCache {
+            max_bytes,
+            total_bytes: 0,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    /// Get the data of the entry like [`VPKEntryHandle::get_with_files`], reading it only if it
+    /// isn't already cached.  
+    /// Entries larger than the whole budget are read but not cached.
+    pub fn get<S>(
+        &mut self,
+        handle: &VPKEntryHandle<'_, S>,
+        prov: &impl VpkReaderProvider,
+    ) -> Result<Arc<[u8]>, Error> {
+        let key = (
+            Arc::as_ptr(&handle.vpk.data).cast::<u8>() as usize,
+            handle.entry.preload_start,
+        );
+        self.tick += 1;
+
+        if let Some(cached) = self.entries.get_mut(&key) {
+            self.order.remove(&cached.last_used);
+            self.order.insert(self.tick, key);
+            cached.last_used = self.tick;
+            return Ok(cached.data.clone());
+        }
+
+        let data: Arc<[u8]> = Arc::from(handle.get_with_files(prov)?);
+        if data.len() > self.max_bytes {
+            return Ok(data);
+        }
+
+        self.total_bytes += data.len();
+        self.evict();
+        self.order.insert(self.tick, key);
+        self.entries.insert(
+            key,
+            CachedEntry {
+                data: data.clone(),
+                _vpk_data: handle.vpk.data.clone(),
+                last_used: self.tick,
+            },
+        );
+
+        Ok(data)
+    }
+
+    /// Evict the least recently used entries until the total is within the budget
+    fn evict(&mut self) {
+        while self.total_bytes > self.max_bytes {
+            let Some((_, key)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some(cached) = self.entries.remove(&key) {
+                self.total_bytes -= cached.data.len();
+            }
+        }
+    }
+
+    /// The number of cached entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The total size of the cached data
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// Remove every cached entry
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.total_bytes = 0;
+    }
+}
+
+impl<S> VPKEntryHandle<'_, S> {
+    /// Get the data of the entry through `cache`, see [`VpkCache::get`].
+    pub fn get_cached(
+        &self,
+        cache: &mut VpkCache,
+        prov: &impl VpkReaderProvider,
+    ) -> Result<Arc<[u8]>, Error> {
+        cache.get(self, prov)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::entry::{ArchiveReadStats, CountingProvider};
+    use crate::vpk::tests::{read_with_archives, TestEntry};
+    use crate::vpk::Ext;
+
+    use super::VpkCache;
+
+    #[test]
+    fn test_vpk_cache() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "a", 0, 0, 3),
+            TestEntry::archive("vtf", "materials", "b", 0, 3, 2),
+            TestEntry::archive("vtf", "materials", "big", 0, 0, 5),
+        ];
+        let (vpk, _dir_file) = read_with_archives("vpk_cache", &entries, &[b"abcde"]);
+        let prov = CountingProvider::new(vpk.lazy_archives());
+        let a = vpk.get(&Ext::Vtf, "materials", "a").unwrap();
+        let b = vpk.get(&Ext::Vtf, "materials", "b").unwrap();
+        let big = vpk.get(&Ext::Vtf, "materials", "big").unwrap();
+
+        let mut cache = VpkCache::new(4);
+        let first = a.get_cached(&mut cache, &prov).unwrap();
+        let second = a.get_cached(&mut cache, &prov).unwrap();
+        assert_eq!(first.as_ref(), b"abc");
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(prov.stats()[&0], ArchiveReadStats { reads: 1, bytes: 3 });
+        assert_eq!((cache.len(), cache.total_bytes()), (1, 3));
+
+        // Over the budget, so `a` is evicted
+        assert_eq!(cache.get(&b, &prov).unwrap().as_ref(), b"de");
+        assert_eq!((cache.len(), cache.total_bytes()), (1, 2));
+        cache.get(&a, &prov).unwrap();
+        assert_eq!(prov.stats()[&0].reads, 3);
+
+        // Larger than the whole budget
+        assert_eq!(cache.get(&big, &prov).unwrap().as_ref(), b"abcde");
+        assert_eq!((cache.len(), cache.total_bytes()), (1, 3));
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.total_bytes(), 0);
+    }
+}
@@ -1,9 +1,16 @@
 use std::borrow::Cow;
+use std::cell::{OnceCell, RefCell};
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::{Error, Read, Seek, SeekFrom};
+use std::io::{BufWriter, Error, Read, Seek, SeekFrom, Write};
 use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use crate::parse::{read_u16, read_u32};
+use crate::access::{path_eq_ignore_case, DirFile, MapRandomState};
+use crate::parse::{read_u16, read_u32, write_u16, write_u32};
+use crate::structs::Endian;
+use crate::vpk::Ext;
 use crate::VPK;
 
 pub trait VpkReaderProvider {
@@ -13,14 +20,273 @@ pub trait VpkReaderProvider {
 
     /// Return a reader for the given archive index.  
     /// Note: if you want the read to continue despite returning an error, then you should just
-    /// ignore the error and return `None`. Any erros will be returned by the `get` function.
+    /// ignore the error and return `None`. Any erros will be returned by the `get` function.  
+    /// Returning `None` makes the read open the archive file at the VPK's archive path, so for an
+    /// archive index that the VPK doesn't have, fail with [`std::io::ErrorKind::NotFound`].
     fn vpk_reader(&self, archive_index: u16) -> std::io::Result<Option<Self::Reader<'_>>>;
 }
 
+/// The archive files, indexed by archive index. See [`VPK::open_all_archive_paths`].
+impl VpkReaderProvider for Vec<Option<File>> {
+    type Reader<'a> = &'a File;
+
+    fn vpk_reader(&self, archive_index: u16) -> std::io::Result<Option<&File>> {
+        Ok(self
+            .get(usize::from(archive_index))
+            .and_then(Option::as_ref))
+    }
+}
+
+/// Opens each archive file the first time it is read from, and keeps it open for later reads.  
+/// This avoids opening a file per entry like [`VPKEntry::get`] does, without opening every archive
+/// up front like [`VPK::open_all_archive_paths`]. See [`VPK::lazy_archives`].  
+/// The files are closed when this is dropped, or early with [`LazyArchives::close_all`].
+#[derive(Debug)]
+pub struct LazyArchives<'a, S = MapRandomState> {
+    vpk: &'a VPK<S>,
+    /// Look for the archive files in this directory rather than at the VPK's archive paths
+    archive_dir: Option<PathBuf>,
+    files: Vec<OnceCell<File>>,
+}
+impl<'a, S> LazyArchives<'a, S> {
+    pub fn new(vpk: &'a VPK<S>) -> LazyArchives<'a, S> {
+        LazyArchives {
+            vpk,
+            archive_dir: None,
+            files: std::iter::repeat_with(OnceCell::new)
+                .take(vpk.archive_paths.len())
+                .collect(),
+        }
+    }
+
+    /// Like [`LazyArchives::new`], but opens the archive files with the same names in
+    /// `archive_dir` instead. This is useful with [`FallbackProvider`] when the archives are
+    /// spread across multiple directories.
+    pub fn in_dir(vpk: &'a VPK<S>, archive_dir: impl Into<PathBuf>) -> LazyArchives<'a, S> {
+        LazyArchives {
+            archive_dir: Some(archive_dir.into()),
+            ..LazyArchives::new(vpk)
+        }
+    }
+
+    /// Close every archive file that has been opened so far. They are opened again if they're
+    /// read from later.
+    pub fn close_all(&mut self) {
+        for cell in &mut self.files {
+            cell.take();
+        }
+    }
+
+    /// The number of archive files that are currently open
+    pub fn open_count(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|cell| cell.get().is_some())
+            .count()
+    }
+}
+impl<S> VpkReaderProvider for LazyArchives<'_, S> {
+    type Reader<'b>
+        = &'b File
+    where
+        Self: 'b;
+
+    fn vpk_reader(&self, archive_index: u16) -> std::io::Result<Option<&File>> {
+        let Some(cell) = self.files.get(usize::from(archive_index)) else {
+            return Err(no_archive(archive_index));
+        };
+
+        if let Some(file) = cell.get() {
+            return Ok(Some(file));
+        }
+
+        let path = self
+            .vpk
+            .archive_path(archive_index)
+            .ok_or_else(|| no_archive(archive_index))?;
+        let path = Path::new(path);
+        let file = match &self.archive_dir {
+            Some(archive_dir) => {
+                File::open(archive_dir.join(path.file_name().unwrap_or_default()))?
+            }
+            None => File::open(path)?,
+        };
+        Ok(Some(cell.get_or_init(|| file)))
+    }
+}
+
+/// The error for an archive index that the VPK has no archive file for
+fn no_archive(archive_index: u16) -> Error {
+    Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("VPK has no archive file with index {archive_index}"),
+    )
+}
+
+fn open_archive<S>(parent: &VPK<S>, archive_index: u16) -> Result<File, Error> {
+    let archive_path = parent
+        .archive_path(archive_index)
+        .ok_or_else(|| no_archive(archive_index))?;
+    File::open(archive_path)
+}
+
+/// Tries each provider in order, using the first one that has the archive file.  
+/// A provider is skipped if it returns `None` or fails with [`std::io::ErrorKind::NotFound`],
+/// like when the archive is in a different directory. Other errors are returned immediately.  
+/// If every provider is skipped then this returns `None`, so the archive file is opened at the
+/// VPK's archive path.
+/// ```rust,ignore
+/// let base = LazyArchives::in_dir(&vpk, "base");
+/// let patch = LazyArchives::in_dir(&vpk, "patch");
+/// let prov = FallbackProvider::new(vec![patch, base]);
+/// let data = handle.get_with_files(&prov)?;
+/// ```
+#[derive(Debug)]
+pub struct FallbackProvider<P> {
+    providers: Vec<P>,
+}
+impl<P> FallbackProvider<P> {
+    pub fn new(providers: Vec<P>) -> FallbackProvider<P> {
+        FallbackProvider { providers }
+    }
+
+    pub fn into_inner(self) -> Vec<P> {
+        self.providers
+    }
+}
+impl<P: VpkReaderProvider> VpkReaderProvider for FallbackProvider<P> {
+    type Reader<'a>
+        = P::Reader<'a>
+    where
+        Self: 'a;
+
+    fn vpk_reader(&self, archive_index: u16) -> std::io::Result<Option<Self::Reader<'_>>> {
+        for prov in &self.providers {
+            match prov.vpk_reader(archive_index) {
+                Ok(Some(reader)) => return Ok(Some(reader)),
+                Ok(None) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// The number of reads and bytes read from an archive file, see [`CountingProvider`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveReadStats {
+    /// The number of times a reader for the archive was requested, typically once per entry read
+    pub reads: u64,
+    /// The number of bytes read from the archive
+    pub bytes: u64,
+}
+
+/// Wraps a [`VpkReaderProvider`] and counts the reads and bytes read from each archive file, for
+/// profiling how a workload uses the archives.  
+/// Note: if the inner provider returns `None` for an archive, then the entry opens the archive
+/// file itself and that isn't counted.
+#[derive(Debug)]
+pub struct CountingProvider<P> {
+    inner: P,
+    stats: RefCell<BTreeMap<u16, ArchiveReadStats>>,
+}
+impl<P> CountingProvider<P> {
+    pub fn new(inner: P) -> CountingProvider<P> {
+        CountingProvider {
+            inner,
+            stats: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// The stats for each archive index that has been read from so far
+    pub fn stats(&self) -> BTreeMap<u16, ArchiveReadStats> {
+        self.stats.borrow().clone()
+    }
+
+    pub fn reset_stats(&self) {
+        self.stats.borrow_mut().clear();
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+impl<P: VpkReaderProvider> VpkReaderProvider for CountingProvider<P> {
+    type Reader<'a>
+        = CountingReader<'a, P::Reader<'a>>
+    where
+        Self: 'a;
+
+    fn vpk_reader(&self, archive_index: u16) -> std::io::Result<Option<Self::Reader<'_>>> {
+        let Some(inner) = self.inner.vpk_reader(archive_index)? else {
+            return Ok(None);
+        };
+
+        self.stats
+            .borrow_mut()
+            .entry(archive_index)
+            .or_default()
+            .reads += 1;
+
+        Ok(Some(CountingReader {
+            inner,
+            archive_index,
+            stats: &self.stats,
+        }))
+    }
+}
+
+/// The reader given by [`CountingProvider`], which counts the bytes read through it.
+#[derive(Debug)]
+pub struct CountingReader<'a, R> {
+    inner: R,
+    archive_index: u16,
+    stats: &'a RefCell<BTreeMap<u16, ArchiveReadStats>>,
+}
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.stats
+            .borrow_mut()
+            .entry(self.archive_index)
+            .or_default()
+            .bytes += read as u64;
+
+        Ok(read)
+    }
+}
+impl<R: Seek> Seek for CountingReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
 // I hate this
 trait ReadSeek: Read + Seek {}
 impl<T: Read + Seek> ReadSeek for T {}
 
+/// Passes writes through, optionally computing the CRC32 of everything written.
+struct Crc32Writer<'a, W> {
+    inner: W,
+    hasher: Option<&'a mut crc32fast::Hasher>,
+}
+impl<W: Write> Write for Crc32Writer<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(&buf[..written]);
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VPKEntry {
     pub dir_entry: VPKDirectoryEntry,
@@ -34,10 +300,72 @@ impl VPKEntry {
         start..end
     }
 
+    /// Where the [`VPKDirectoryEntry`] of this entry is in the dir file, right before the
+    /// preload data.
+    pub fn dir_entry_interval(&self) -> Range<usize> {
+        self.preload_start.saturating_sub(VPKDirectoryEntry::SIZE)..self.preload_start
+    }
+
+    /// The [`VPKDirectoryEntry`] exactly as it is stored in the dir file, in the VPK's byte order.
+    pub fn raw_dir_entry_bytes<'v, S>(&self, parent: &'v VPK<S>) -> &'v [u8] {
+        &parent.data[self.dir_entry_interval()]
+    }
+
+    /// The rest of the data of an entry stored in the dir file, after its preload data.  
+    /// In a v2 VPK this is in the embed chunk, at `archive_offset`. Only `Some` for entries with
+    /// an archive index of `0x7fff` and a non-zero `file_length`.
+    pub fn inline_interval(&self) -> Option<Range<usize>> {
+        if self.dir_entry.archive_index != 0x7fff || self.dir_entry.file_length == 0 {
+            return None;
+        }
+
+        let start = self.dir_entry.archive_offset as usize;
+        let end = start + self.dir_entry.file_length as usize;
+        Some(start..end)
+    }
+
+    /// The data of the entry in the dir file after its preload data, see
+    /// [`VPKEntry::inline_interval`].
+    fn inline_data<'v, S>(&self, parent: &'v VPK<S>) -> Result<&'v [u8], Error> {
+        let Some(inline) = self.inline_interval() else {
+            return Ok(&[]);
+        };
+
+        parent.data.get(inline).ok_or_else(|| {
+            Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Inline data of the entry is past the end of the VPK data",
+            )
+        })
+    }
+
     pub fn archive_index(&self) -> u16 {
         self.dir_entry.archive_index
     }
 
+    /// The full size of the file's data, both the preload data and the data in the archive.
+    pub fn total_size(&self) -> u64 {
+        u64::from(self.dir_entry.file_length) + u64::from(self.dir_entry.preload_length)
+    }
+
+    #[cfg(feature = "tracing")]
+    fn archive_read_span(&self) -> tracing::span::EnteredSpan {
+        tracing::trace_span!(
+            "archive_read",
+            archive_index = self.dir_entry.archive_index,
+            offset = self.dir_entry.archive_offset,
+            length = self.dir_entry.file_length,
+        )
+        .entered()
+    }
+
+    /// Whether the entry has no data at all, neither preload data nor data in an archive.  
+    /// Entries stored inline in the dir file usually have a `file_length` of zero, with their data
+    /// all in the preload, so they are only empty if the preload is empty too.
+    pub fn is_empty(&self) -> bool {
+        self.total_size() == 0
+    }
+
     /// Get the data in the [`VPKEntry`]
     /// If this is preloaded data, aka the data is stored in the directory file, then it can easily
     /// return a `Cow::Borrowed`. Typically this is only small files, like `vmt`s.
@@ -47,9 +375,9 @@ impl VPKEntry {
     /// file.
     /// If `files` does contain the archive file for this entry, then it will use that file. This is
     /// useful if you want to read multiple files from the same archive file.
-    pub fn get_with_files<'v>(
+    pub fn get_with_files<'v, S>(
         &self,
-        parent: &'v VPK,
+        parent: &'v VPK<S>,
         prov: &impl VpkReaderProvider,
     ) -> Result<Cow<'v, [u8]>, Error> {
         if self.dir_entry.archive_index == 0x7fff {
@@ -62,31 +390,57 @@ impl VPKEntry {
         }
     }
 
+    /// Like [`VPKEntry::get_with_files`], but the data doesn't borrow from `parent`, so it can
+    /// outlive the [`VPK`]. Data stored in the dir file is copied, data read from an archive
+    /// file is already owned.
+    pub fn get_static<S>(
+        &self,
+        parent: &VPK<S>,
+        prov: &impl VpkReaderProvider,
+    ) -> Result<Cow<'static, [u8]>, Error> {
+        let data = self.get_with_files(parent, prov)?;
+        Ok(Cow::Owned(data.into_owned()))
+    }
+
     /// Get the data in the [`VPKEntry`]  
     /// If this is preloaded data, aka the data is stored in the directory file, then it can easily
     /// return a `Cow::Borrowed`. Typically this is only small files, like `vmt`s.  
     /// For other files, it has to open the resident archive file and read the requisite data.  
     ///   
-    /// If `file` is `None`, then it will open the archive file.
+    /// If `file` is `None`, then it will open the archive file, and close it once done.
     /// If `file` is `Some`, then it will use that file. This is useful if you want to read multiple
     /// files from the same archive file.
-    pub fn get_with_file<'v, R: Read + Seek>(
+    pub fn get_with_file<'v, R: Read + Seek, S>(
         &self,
-        parent: &'v VPK,
+        parent: &'v VPK<S>,
         mut reader: Option<R>,
     ) -> Result<Cow<'v, [u8]>, Error> {
         if self.dir_entry.archive_index == 0x7fff {
             let preload_data = &parent.data[self.preload_interval()];
-            return Ok(Cow::Borrowed(preload_data));
+            let inline_data = self.inline_data(parent)?;
+            return Ok(if inline_data.is_empty() {
+                Cow::Borrowed(preload_data)
+            } else if preload_data.is_empty() {
+                Cow::Borrowed(inline_data)
+            } else {
+                Cow::Owned([preload_data, inline_data].concat())
+            });
         }
 
+        if self.dir_entry.file_length == 0 {
+            // No need to open the archive file just to read nothing
+            return Ok(Cow::Borrowed(&[]));
+        }
+
+        #[cfg(feature = "tracing")]
+        let _span = self.archive_read_span();
+
         let mut buf = vec![0; self.dir_entry.file_length as usize];
         let mut tmp;
         let file: &mut dyn ReadSeek = if let Some(file) = reader.as_mut() {
             &mut *file
         } else {
-            let archive_path = &parent.archive_paths[usize::from(self.dir_entry.archive_index)];
-            tmp = File::open(archive_path)?;
+            tmp = open_archive(parent, self.dir_entry.archive_index)?;
             &mut tmp
         };
         file.seek(SeekFrom::Start(self.dir_entry.archive_offset as u64))?;
@@ -97,9 +451,167 @@ impl VPKEntry {
     /// Get the data in the [`VPKEntry`]
     /// If this is preloaded data, aka the data is stored in the directory file, then it can easily
     /// return a `Cow::Borrowed`. Typically this is only small files, like `vmt`s.
-    /// For other files, it has to open the resident archive file and read the requisite data.
-    pub fn get<'v>(&self, parent: &'v VPK) -> Result<Cow<'v, [u8]>, Error> {
-        self.get_with_file::<File>(parent, None)
+    /// For other files, it has to open the resident archive file and read the requisite data.  
+    ///   
+    /// Note: this opens and closes the archive file on every call. If you are reading many
+    /// entries then use [`VPKEntry::get_with_files`] with [`VPK::lazy_archives`] or
+    /// [`VPK::open_all_archive_paths`] so that each archive is only opened once.
+    pub fn get<'v, S>(&self, parent: &'v VPK<S>) -> Result<Cow<'v, [u8]>, Error> {
+        self.get_with_file::<File, S>(parent, None)
+    }
+
+    /// Write the data in the [`VPKEntry`] to `writer`, streaming it from the archive file rather
+    /// than reading it all into memory.  
+    /// Unlike [`VPKEntry::get`], this includes the preload data for entries in archive files, so
+    /// this writes out the whole file.  
+    /// Returns the number of bytes written.
+    pub fn copy_to<S>(
+        &self,
+        parent: &VPK<S>,
+        prov: &impl VpkReaderProvider,
+        writer: &mut impl Write,
+    ) -> Result<u64, Error> {
+        self.copy_to_inner(parent, prov, writer, None)
+    }
+
+    /// Check whether this entry has exactly the same data as `other`, which may be from a
+    /// different [`VPK`].  
+    /// This first compares the sizes and stored CRC32s, and only reads and compares the data if
+    /// those match, so obviously different entries are cheap.  
+    /// `prov` is used to read both entries. If they're from different VPKs, then use a provider
+    /// that doesn't return readers (like an empty `Vec<Option<File>>`) so each archive file is opened
+    /// from its own VPK's paths.
+    pub fn same_data_as<S>(
+        &self,
+        parent: &VPK<S>,
+        other: &VPKEntry,
+        other_parent: &VPK<S>,
+        prov: &impl VpkReaderProvider,
+    ) -> Result<bool, Error> {
+        if self.total_size() != other.total_size() || self.dir_entry.crc32 != other.dir_entry.crc32
+        {
+            return Ok(false);
+        }
+
+        let mut data = Vec::with_capacity(self.total_size() as usize);
+        self.copy_to(parent, prov, &mut data)?;
+        let mut other_data = Vec::with_capacity(other.total_size() as usize);
+        other.copy_to(other_parent, prov, &mut other_data)?;
+
+        Ok(data == other_data)
+    }
+
+    /// Like [`VPKEntry::copy_to`], but also computes the CRC32 of the data as it is written.  
+    /// Returns the number of bytes written and whether the CRC32 matched the stored one.
+    pub fn copy_to_verified<S>(
+        &self,
+        parent: &VPK<S>,
+        prov: &impl VpkReaderProvider,
+        writer: &mut impl Write,
+    ) -> Result<(u64, bool), Error> {
+        let mut hasher = crc32fast::Hasher::new();
+        let written = self.copy_to_inner(parent, prov, writer, Some(&mut hasher))?;
+
+        Ok((written, hasher.finalize() == self.dir_entry.crc32))
+    }
+
+    fn copy_to_inner<S>(
+        &self,
+        parent: &VPK<S>,
+        prov: &impl VpkReaderProvider,
+        writer: &mut impl Write,
+        hasher: Option<&mut crc32fast::Hasher>,
+    ) -> Result<u64, Error> {
+        let mut writer = Crc32Writer {
+            inner: writer,
+            hasher,
+        };
+
+        let preload_data = &parent.data[self.preload_interval()];
+        writer.write_all(preload_data)?;
+        let mut written = preload_data.len() as u64;
+
+        if self.dir_entry.archive_index == 0x7fff {
+            let inline_data = self.inline_data(parent)?;
+            writer.write_all(inline_data)?;
+            return Ok(written + inline_data.len() as u64);
+        }
+
+        if self.dir_entry.file_length == 0 {
+            return Ok(written);
+        }
+
+        #[cfg(feature = "tracing")]
+        let _span = self.archive_read_span();
+
+        let mut reader = prov.vpk_reader(self.dir_entry.archive_index)?;
+        let mut tmp;
+        let file: &mut dyn ReadSeek = if let Some(file) = reader.as_mut() {
+            file
+        } else {
+            tmp = open_archive(parent, self.dir_entry.archive_index)?;
+            &mut tmp
+        };
+        file.seek(SeekFrom::Start(self.dir_entry.archive_offset as u64))?;
+
+        let file_length = u64::from(self.dir_entry.file_length);
+        let copied = std::io::copy(&mut file.take(file_length), &mut writer)?;
+        if copied != file_length {
+            return Err(Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Archive file ended before the end of the entry",
+            ));
+        }
+        written += copied;
+
+        Ok(written)
+    }
+
+    /// Compute the CRC32 of the entry, given the `data` returned by one of the `get` functions.  
+    /// For entries in an archive file this also covers the preload data.
+    pub fn compute_crc32<S>(&self, parent: &VPK<S>, data: &[u8]) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        if self.dir_entry.archive_index != 0x7fff {
+            hasher.update(&parent.data[self.preload_interval()]);
+        }
+        hasher.update(data);
+
+        hasher.finalize()
+    }
+
+    /// Compute the MD5 of the entry, given the `data` returned by one of the `get` functions.  
+    /// Like [`VPKEntry::compute_crc32`], this also covers the preload data.
+    pub fn compute_md5<S>(&self, parent: &VPK<S>, data: &[u8]) -> [u8; 16] {
+        let mut context = md5::Context::new();
+        if self.dir_entry.archive_index != 0x7fff {
+            context.consume(&parent.data[self.preload_interval()]);
+        }
+        context.consume(data);
+
+        context.compute().0
+    }
+
+    /// Get the data in the [`VPKEntry`] like [`VPKEntry::get_with_files`], and check it against the
+    /// stored CRC32.  
+    /// On a mismatch this returns [`crate::Error::ChecksumMismatch`], which still holds the data.
+    pub fn read_verified<'v, S>(
+        &self,
+        parent: &'v VPK<S>,
+        prov: &impl VpkReaderProvider,
+    ) -> Result<Cow<'v, [u8]>, crate::Error> {
+        let data = self.get_with_files(parent, prov)?;
+
+        let expected = self.dir_entry.crc32;
+        let actual = self.compute_crc32(parent, &data);
+        if expected != actual {
+            return Err(crate::Error::ChecksumMismatch {
+                expected,
+                actual,
+                data: data.into_owned(),
+            });
+        }
+
+        Ok(data)
     }
 }
 
@@ -113,13 +625,24 @@ pub struct VPKDirectoryEntry {
     pub suffix: u16,
 }
 impl VPKDirectoryEntry {
+    /// The size of the entry in the dir file
+    pub const SIZE: usize = 18;
+
     pub fn read_le(r: &mut impl Read) -> std::io::Result<Self> {
-        let crc32 = read_u32(r)?;
-        let preload_length = read_u16(r)?;
-        let archive_index = read_u16(r)?;
-        let archive_offset = read_u32(r)?;
-        let file_length = read_u32(r)?;
-        let suffix = read_u16(r)?;
+        Self::read(r, Endian::Little)
+    }
+
+    pub fn read_be(r: &mut impl Read) -> std::io::Result<Self> {
+        Self::read(r, Endian::Big)
+    }
+
+    pub fn read(r: &mut impl Read, endian: Endian) -> std::io::Result<Self> {
+        let crc32 = read_u32(r, endian)?;
+        let preload_length = read_u16(r, endian)?;
+        let archive_index = read_u16(r, endian)?;
+        let archive_offset = read_u32(r, endian)?;
+        let file_length = read_u32(r, endian)?;
+        let suffix = read_u16(r, endian)?;
 
         Ok(Self {
             crc32,
@@ -130,18 +653,117 @@ impl VPKDirectoryEntry {
             suffix,
         })
     }
+
+    /// Check that the entry is well formed: it has to end with the `0xffff` suffix, and its data
+    /// has to end within the 4 GiB that an archive offset can address.  
+    /// This is for the entry as it is stored in the dir file, so for entries with an archive
+    /// index of `0x7fff` the offset is relative to the end of the tree, see
+    /// [`VPKDirectoryEntry::validate_inline`] for their other constraint.  
+    /// The parser checks every entry with this, so anything writing entries should too.
+    pub fn validate(&self) -> Result<(), crate::Error> {
+        if self.suffix != 0xffff {
+            return Err(crate::Error::InvalidEntry {
+                reason: "the suffix is not 0xffff",
+            });
+        }
+
+        if self.archive_offset.checked_add(self.file_length).is_none() {
+            return Err(crate::Error::InvalidEntry {
+                reason: "the data ends past the largest archive offset",
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Check that the data of an entry stored in the dir file, with an archive index of `0x7fff`,
+    /// is within the `inline_length` bytes after the tree. This is the whole rest of the file,
+    /// since the embed chunk of a version 2 VPK comes right after the tree.  
+    /// Like [`VPKDirectoryEntry::validate`], the offset is relative to the end of the tree as it
+    /// is in the dir file, rather than to the start of the file as in a read [`VPK`].  
+    /// Always `Ok` for entries stored in archive files.
+    pub fn validate_inline(&self, inline_length: u64) -> Result<(), crate::Error> {
+        if self.archive_index != 0x7fff || self.file_length == 0 {
+            return Ok(());
+        }
+
+        let end = u64::from(self.archive_offset) + u64::from(self.file_length);
+        if end > inline_length {
+            return Err(crate::Error::InvalidEntry {
+                reason: "the data ends past the end of the dir file",
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Write the 18 byte entry, the inverse of [`VPKDirectoryEntry::read_le`]
+    pub fn write_le(&self, w: &mut impl Write) -> std::io::Result<()> {
+        self.write(w, Endian::Little)
+    }
+
+    pub fn write_be(&self, w: &mut impl Write) -> std::io::Result<()> {
+        self.write(w, Endian::Big)
+    }
+
+    pub fn write(&self, w: &mut impl Write, endian: Endian) -> std::io::Result<()> {
+        write_u32(w, self.crc32, endian)?;
+        write_u16(w, self.preload_length, endian)?;
+        write_u16(w, self.archive_index, endian)?;
+        write_u32(w, self.archive_offset, endian)?;
+        write_u32(w, self.file_length, endian)?;
+        write_u16(w, self.suffix, endian)?;
+
+        Ok(())
+    }
+}
+
+/// Shows the size and where the data is, like `1.2 KiB, archive 0`
+impl std::fmt::Display for VPKEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, ", HumanSize(self.total_size()))?;
+        if self.dir_entry.archive_index == 0x7fff {
+            write!(f, "inline")
+        } else {
+            write!(f, "archive {}", self.dir_entry.archive_index)
+        }
+    }
+}
+
+/// Formats a byte count like `1.2 KiB`
+struct HumanSize(u64);
+impl std::fmt::Display for HumanSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+
+        let mut size = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+
+        write!(f, "{:.1} {}", size, UNITS[unit])
+    }
 }
 
 /// A handle holds both the [`VPK`] and a held [`VPKEntry`].
 /// This is useful for [`VPKEntry::get`] where the [`VPKEntry`] needs to know
 /// the parent data.
 #[derive(Debug)]
-pub struct VPKEntryHandle<'a> {
+pub struct VPKEntryHandle<'a, S = MapRandomState> {
     /// The [`VPK`] that holds this [`VPKEntry`]
-    pub vpk: &'a VPK,
+    pub vpk: &'a VPK<S>,
+    /// The extension of the entry, which isn't stored in the [`VPKEntry`] itself
+    pub ext: Ext<'a>,
+    /// The dir and filename of the entry, as they are stored in the VPK
+    pub dir_file: &'a DirFile,
     pub entry: &'a VPKEntry,
 }
-impl<'a> VPKEntryHandle<'a> {
+impl<'a, S> VPKEntryHandle<'a, S> {
     /// Get the data in the [`VPKEntry`]
     /// If this is preloaded data, aka the data is stored in the directory file, then it can easily
     /// return a `Cow::Borrowed`. Typically this is only small files, like `vmt`s.
@@ -160,7 +782,7 @@ impl<'a> VPKEntryHandle<'a> {
     /// return a `Cow::Borrowed`. Typically this is only small files, like `vmt`s.  
     /// For other files, it has to open the resident archive file and read the requisite data.  
     ///   
-    /// If `file` is `None`, then it will open the archive file.
+    /// If `file` is `None`, then it will open the archive file, and close it once done.
     /// If `file` is `Some`, then it will use that file. This is useful if you want to read multiple
     /// files from the same archive file.
     pub fn get_with_file<R: Read + Seek>(&self, file: Option<R>) -> Result<Cow<'a, [u8]>, Error> {
@@ -170,7 +792,11 @@ impl<'a> VPKEntryHandle<'a> {
     /// Get the data in the [`VPKEntry`]
     /// If this is preloaded data, aka the data is stored in the directory file, then it can easily
     /// return a `Cow::Borrowed`. Typically this is only small files, like `vmt`s.
-    /// For other files, it has to open the resident archive file and read the requisite data.
+    /// For other files, it has to open the resident archive file and read the requisite data.  
+    ///   
+    /// Note: this opens and closes the archive file on every call. If you are reading many
+    /// entries then use [`VPKEntryHandle::get_with_files`] with [`VPK::lazy_archives`] so that
+    /// each archive is only opened once.
     pub fn get(&self) -> Result<Cow<'a, [u8]>, Error> {
         self.entry.get(self.vpk)
     }
@@ -179,6 +805,161 @@ impl<'a> VPKEntryHandle<'a> {
         self.entry.archive_index()
     }
 
+    /// The CRC32 of the entry's data, as stored in the VPK
+    pub fn crc32(&self) -> u32 {
+        self.entry.dir_entry.crc32
+    }
+
+    /// Get the data in the [`VPKEntry`] and check it against the stored CRC32.  
+    /// See [`VPKEntry::read_verified`].
+    pub fn read_verified(
+        &self,
+        prov: &impl VpkReaderProvider,
+    ) -> Result<Cow<'a, [u8]>, crate::Error> {
+        self.entry.read_verified(self.vpk, prov)
+    }
+
+    /// Write the whole data of the [`VPKEntry`] to `writer`. See [`VPKEntry::copy_to`].
+    pub fn copy_to(
+        &self,
+        prov: &impl VpkReaderProvider,
+        writer: &mut impl Write,
+    ) -> Result<u64, Error> {
+        self.entry.copy_to(self.vpk, prov, writer)
+    }
+
+    /// Write the whole data of the [`VPKEntry`] to the file at `path`, creating its parent
+    /// directories if needed. See [`VPKEntry::copy_to`].  
+    /// Returns the number of bytes written.
+    pub fn save_to(
+        &self,
+        path: impl AsRef<Path>,
+        prov: &impl VpkReaderProvider,
+    ) -> Result<u64, Error> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut file = BufWriter::new(File::create(path)?);
+        let written = self.copy_to(prov, &mut file)?;
+        file.flush()?;
+
+        Ok(written)
+    }
+
+    /// Write the whole data of the [`VPKEntry`] to `writer` while checking its CRC32.  
+    /// See [`VPKEntry::copy_to_verified`].
+    pub fn copy_to_verified(
+        &self,
+        prov: &impl VpkReaderProvider,
+        writer: &mut impl Write,
+    ) -> Result<(u64, bool), Error> {
+        self.entry.copy_to_verified(self.vpk, prov, writer)
+    }
+
+    /// Read the data in the [`VPKEntry`] and check whether its MD5 is `expected_md5`.  
+    /// VPKs only store a CRC32 for each entry, so this is for checking against hashes from some
+    /// other source, like a manifest.
+    pub fn verify_hash(
+        &self,
+        expected_md5: [u8; 16],
+        prov: &impl VpkReaderProvider,
+    ) -> Result<bool, Error> {
+        let data = self.get_with_files(prov)?;
+        Ok(self.entry.compute_md5(self.vpk, &data) == expected_md5)
+    }
+
+    /// Just the preload data stored in the dir file, which may be empty.  
+    /// Unlike [`VPKEntryHandle::get`], this never reads from an archive file, even if the entry
+    /// has more data in one, so it is cheap for peeking at the start of files like headers.
+    pub fn preload_bytes(&self) -> &'a [u8] {
+        &self.vpk.data[self.entry.preload_interval()]
+    }
+
+    /// The extension, like `vmt`. An entry with no extension has a single space.
+    pub fn ext_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.ext.as_slice())
+    }
+
+    /// The filename with its extension but without the dir, like `concretefloor001a.vmt`.
+    pub fn filename_with_ext(&self) -> String {
+        let filename = String::from_utf8_lossy(self.dir_file.filename());
+        if self.ext.as_slice() == b" " {
+            filename.into_owned()
+        } else {
+            format!("{}.{}", filename, self.ext_str())
+        }
+    }
+
+    /// The full path of the entry, like `materials/concrete/concretefloor001a.vmt`.  
+    /// The dir is left out if the entry doesn't have one.
+    pub fn full_path(&self) -> String {
+        let dir = self.dir_file.dir_or_empty();
+        if dir.is_empty() {
+            self.filename_with_ext()
+        } else {
+            format!(
+                "{}/{}",
+                String::from_utf8_lossy(dir),
+                self.filename_with_ext()
+            )
+        }
+    }
+
+    /// The full path of the entry relative to the dir `root`, like `concrete/floor001a.vmt` for
+    /// `materials/concrete/floor001a.vmt` relative to `materials/`.  
+    /// `root` is compared case-insensitively, and may end with a separator. Returns `None` if the
+    /// entry isn't in `root` or one of its subdirectories.
+    pub fn relative_to(&self, root: &str) -> Option<String> {
+        let root = root.trim_end_matches(['/', '\\']).as_bytes();
+        if root.is_empty() {
+            return Some(self.full_path());
+        }
+
+        let dir = self.dir_file.dir();
+        let rest = dir
+            .get(..root.len())
+            .filter(|start| path_eq_ignore_case(start, root))
+            .map(|_| &dir[root.len()..])?;
+        match rest {
+            [] => Some(self.filename_with_ext()),
+            [b'/' | b'\\', rest @ ..] if !rest.is_empty() => Some(format!(
+                "{}/{}",
+                String::from_utf8_lossy(rest),
+                self.filename_with_ext()
+            )),
+            _ => None,
+        }
+    }
+
+    /// Read the data in the [`VPKEntry`] once, giving something that can be used as a `&[u8]`.  
+    /// See [`VPKEntryHandle::get_with_files`].
+    pub fn load(self, prov: &impl VpkReaderProvider) -> Result<LoadedEntry<'a>, Error> {
+        let data = self.get_with_files(prov)?;
+        Ok(LoadedEntry {
+            entry: self.entry,
+            data,
+        })
+    }
+
+    /// An id for the entry that is unique within its VPK, for keeping track of an entry without
+    /// holding on to the handle, like the selected entry in a UI.  
+    /// This is where the entry is in the dir file, so it stays the same across clones of the
+    /// [`VPK`] and reading the same dir file again.
+    pub fn id(&self) -> usize {
+        self.entry.dir_entry_interval().start
+    }
+
+    /// Read the data like [`VPKEntryHandle::get_with_files`], detached from the [`VPK`] so that it
+    /// can outlive it. See [`VPKEntry::get_static`].
+    pub fn into_cow_static(
+        self,
+        prov: &impl VpkReaderProvider,
+    ) -> Result<Cow<'static, [u8]>, Error> {
+        self.entry.get_static(self.vpk, prov)
+    }
+
     /// Only returns `None` if the `archive_index` is `0x7fff`  
     ///   
     /// # Panics
@@ -190,7 +971,1039 @@ impl<'a> VPKEntryHandle<'a> {
             return None;
         }
 
-        let archive_index = usize::from(self.entry.dir_entry.archive_index);
-        Some(&self.vpk.archive_paths[archive_index])
+        let archive_path = self.vpk.archive_path(self.entry.dir_entry.archive_index);
+        Some(archive_path.expect("Entries in the VPK have a path for their archive"))
+    }
+
+    /// Just the file name of the archive file, like `pak01_007.vpk`, without its directory.  
+    /// See [`VPKEntryHandle::archive_path`].
+    pub fn archive_relative_path(&self) -> Option<&str> {
+        let archive_path = self.archive_path()?;
+        Path::new(archive_path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .or(Some(archive_path))
+    }
+
+    /// Read and decode the entry as an image, for the formats that the `image` crate understands
+    /// (currently `bmp` and `ico`).  
+    /// Other extensions give an unsupported [`image::ImageError`]. Notably `vtf` textures are a
+    /// Valve format that `image` can't decode, so they need a separate VTF decoder.
+    #[cfg(feature = "image")]
+    pub fn decode_image(
+        &self,
+        prov: &impl VpkReaderProvider,
+    ) -> Result<image::DynamicImage, crate::Error> {
+        use image::error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+        use image::ImageFormat;
+
+        let format = match self.ext {
+            Ext::Bmp => ImageFormat::Bmp,
+            Ext::Ico => ImageFormat::Ico,
+            _ => {
+                let hint = ImageFormatHint::Name(self.ext_str().into_owned());
+                let err = UnsupportedError::from_format_and_kind(
+                    hint.clone(),
+                    UnsupportedErrorKind::Format(hint),
+                );
+                return Err(image::ImageError::Unsupported(err).into());
+            }
+        };
+
+        let data = self.get_with_files(prov)?;
+        Ok(image::load_from_memory_with_format(&data, format)?)
+    }
+
+    /// Read the entry as UTF-8 text, like a `vmt` or `txt`.  
+    /// Fails with [`crate::Error::InvalidUtf8`] if it isn't valid UTF-8. Older files are often
+    /// Windows-1252 instead, see [`VPKEntryHandle::read_to_string_lossy`] and, with the
+    /// `encoding_rs` feature, `read_to_string_cp1252`.
+    pub fn read_to_string(&self, prov: &impl VpkReaderProvider) -> Result<String, crate::Error> {
+        let data = self.get_with_files(prov)?.into_owned();
+        String::from_utf8(data).map_err(|err| crate::Error::InvalidUtf8 {
+            path: self.full_path(),
+            error: err.utf8_error(),
+        })
+    }
+
+    /// Read the entry as UTF-8 text, replacing any invalid UTF-8 with `�`.
+    pub fn read_to_string_lossy(
+        &self,
+        prov: &impl VpkReaderProvider,
+    ) -> Result<String, crate::Error> {
+        let data = self.get_with_files(prov)?;
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+
+    /// Read the entry as Windows-1252 text, which every byte is valid in.
+    #[cfg(feature = "encoding_rs")]
+    pub fn read_to_string_cp1252(
+        &self,
+        prov: &impl VpkReaderProvider,
+    ) -> Result<String, crate::Error> {
+        let data = self.get_with_files(prov)?;
+        let (text, _) = encoding_rs::WINDOWS_1252.decode_without_bom_handling(&data);
+        Ok(text.into_owned())
+    }
+
+    /// Get a reader over the data of the entry, for streaming large entries rather than reading
+    /// them into memory all at once.  
+    /// This opens the archive file if the entry has data in one, see
+    /// [`VPKEntryHandle::to_reader_with_file`] to use a file that is already open.
+    pub fn to_reader(&self) -> Result<VpkEntryReader<'a>, Error> {
+        let archive = match self.archive_path() {
+            Some(archive_path) if self.entry.dir_entry.file_length != 0 => {
+                Some(File::open(archive_path)?)
+            }
+            _ => None,
+        };
+
+        Ok(VpkEntryReader::new(
+            self.preload_bytes(),
+            self.entry.inline_data(self.vpk)?,
+            self.entry,
+            archive,
+        ))
+    }
+
+    /// Get a reader over the data of the entry, reading the archive data from `reader`.  
+    /// `reader` should be the archive file of this entry, for example from
+    /// [`VpkReaderProvider::vpk_reader`]. It is unused if the entry is stored in the dir file.
+    pub fn to_reader_with_file<R: Read + Seek>(
+        &self,
+        reader: R,
+    ) -> Result<VpkEntryReader<'a, R>, Error> {
+        let archive = (self.entry.dir_entry.archive_index != 0x7fff).then_some(reader);
+        Ok(VpkEntryReader::new(
+            self.preload_bytes(),
+            self.entry.inline_data(self.vpk)?,
+            self.entry,
+            archive,
+        ))
+    }
+
+    /// Check whether the archive file for this entry exists, without opening it.  
+    /// This is always `true` for entries stored in the dir file. Like [`Path::exists`], this is
+    /// `false` if the existence can't be checked, like from a permission error.
+    pub fn archive_file_exists(&self) -> bool {
+        self.archive_path()
+            .map_or(true, |archive_path| Path::new(archive_path).exists())
+    }
+}
+
+/// Handles are equal if they are for the same entry of the same dir file, see
+/// [`VPKEntryHandle::id`].
+impl<S> PartialEq for VPKEntryHandle<'_, S> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.vpk.data, &other.vpk.data) && self.id() == other.id()
+    }
+}
+impl<S> Eq for VPKEntryHandle<'_, S> {}
+impl<S> std::hash::Hash for VPKEntryHandle<'_, S> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+/// Shows the path, size and where the data is, like `materials/x.vmt (1.2 KiB, archive 0)`
+impl<S> std::fmt::Display for VPKEntryHandle<'_, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.full_path(), self.entry)
+    }
+}
+
+/// Reads the data of an entry, the preload data followed by the data in the archive file.  
+/// See [`VPKEntryHandle::to_reader`].
+#[derive(Debug)]
+pub struct VpkEntryReader<'a, R = File> {
+    preload: &'a [u8],
+    /// The data after the preload data for entries stored in the dir file
+    inline: &'a [u8],
+    /// `None` if the entry has no data in an archive file
+    archive: Option<R>,
+    archive_offset: u64,
+    archive_length: u64,
+    /// The position in the entry's data
+    pos: u64,
+    /// The position in the entry's archive data that `archive` is at, if known, to avoid seeking
+    /// before every read
+    archive_pos: Option<u64>,
+}
+impl<'a, R: Read + Seek> VpkEntryReader<'a, R> {
+    fn new(
+        preload: &'a [u8],
+        inline: &'a [u8],
+        entry: &VPKEntry,
+        archive: Option<R>,
+    ) -> VpkEntryReader<'a, R> {
+        let archive_length = if archive.is_some() {
+            u64::from(entry.dir_entry.file_length)
+        } else {
+            0
+        };
+
+        VpkEntryReader {
+            preload,
+            inline,
+            archive,
+            archive_offset: u64::from(entry.dir_entry.archive_offset),
+            archive_length,
+            pos: 0,
+            archive_pos: None,
+        }
+    }
+
+    /// The total length of the entry's data
+    pub fn len(&self) -> u64 {
+        self.preload.len() as u64 + self.inline.len() as u64 + self.archive_length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the archive reader back, if the entry has data in an archive file
+    pub fn into_inner(self) -> Option<R> {
+        self.archive
+    }
+}
+impl<R: Read + Seek> Read for VpkEntryReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let preload_length = self.preload.len() as u64;
+        let in_memory = if self.pos < preload_length {
+            Some(&self.preload[self.pos as usize..])
+        } else if self.archive.is_none() {
+            let inline_pos = self.pos - preload_length;
+            Some(if inline_pos < self.inline.len() as u64 {
+                &self.inline[inline_pos as usize..]
+            } else {
+                &[]
+            })
+        } else {
+            None
+        };
+        if let Some(rest) = in_memory {
+            let read = rest.len().min(buf.len());
+            buf[..read].copy_from_slice(&rest[..read]);
+            self.pos += read as u64;
+            return Ok(read);
+        }
+
+        let archive_pos = self.pos - preload_length;
+        let Some(archive) = self.archive.as_mut() else {
+            return Ok(0);
+        };
+        if archive_pos >= self.archive_length {
+            return Ok(0);
+        }
+
+        if self.archive_pos != Some(archive_pos) {
+            archive.seek(SeekFrom::Start(self.archive_offset + archive_pos))?;
+        }
+
+        let max = (self.archive_length - archive_pos).min(buf.len() as u64) as usize;
+        let read = match archive.read(&mut buf[..max]) {
+            Ok(read) => read,
+            Err(err) => {
+                // We don't know how far it got
+                self.archive_pos = None;
+                return Err(err);
+            }
+        };
+        if read == 0 && max != 0 {
+            return Err(Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Archive file ended before the end of the entry",
+            ));
+        }
+
+        self.pos += read as u64;
+        self.archive_pos = Some(archive_pos + read as u64);
+
+        Ok(read)
+    }
+}
+impl<R: Read + Seek> Seek for VpkEntryReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+        };
+        let Some(pos) = pos else {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            ));
+        };
+
+        // Like files, seeking past the end is allowed, and reads there just return nothing
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+/// The data of a [`VPKEntry`] that has already been read, from [`VPKEntryHandle::load`].
+#[derive(Debug, Clone)]
+pub struct LoadedEntry<'a> {
+    pub entry: &'a VPKEntry,
+    data: Cow<'a, [u8]>,
+}
+impl<'a> LoadedEntry<'a> {
+    pub fn into_inner(self) -> Cow<'a, [u8]> {
+        self.data
+    }
+}
+impl AsRef<[u8]> for LoadedEntry<'_> {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+impl std::ops::Deref for LoadedEntry<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    use super::{
+        ArchiveReadStats, CountingProvider, FallbackProvider, LazyArchives, VPKDirectoryEntry,
+        VpkReaderProvider,
+    };
+    use crate::vpk::tests::{
+        build_vpk_v1, build_vpk_v2, read_with_archives, temp_dir, OpenArchives, TestEntry,
+    };
+    use crate::vpk::{Ext, ProbableKind};
+    use crate::{Error, VPK};
+
+    #[test]
+    fn test_read_verified() {
+        let entries = [
+            TestEntry {
+                crc32: crc32fast::hash(b"abc"),
+                ..TestEntry::preload("vmt", "materials", "good", b"abc")
+            },
+            TestEntry {
+                crc32: 1234,
+                ..TestEntry::preload("vmt", "materials", "bad", b"abc")
+            },
+            TestEntry {
+                crc32: crc32fast::hash(b"pre-def"),
+                preload: b"pre-",
+                ..TestEntry::archive("vtf", "materials", "split", 0, 1, 3)
+            },
+        ];
+        let (vpk, _dir_file) = read_with_archives("read_verified", &entries, &[b".def"]);
+
+        let good = vpk.get(&Ext::Vmt, "materials", "good").unwrap();
+        assert_eq!(good.crc32(), crc32fast::hash(b"abc"));
+        assert_eq!(good.read_verified(&OpenArchives).unwrap().as_ref(), b"abc");
+
+        let bad = vpk.get(&Ext::Vmt, "materials", "bad").unwrap();
+        match bad.read_verified(&OpenArchives) {
+            Err(Error::ChecksumMismatch {
+                expected,
+                actual,
+                data,
+            }) => {
+                assert_eq!(expected, 1234);
+                assert_eq!(actual, crc32fast::hash(b"abc"));
+                assert_eq!(data, b"abc");
+            }
+            res => panic!("expected a checksum mismatch, got {:?}", res),
+        }
+
+        let split = vpk.get(&Ext::Vtf, "materials", "split").unwrap();
+        assert_eq!(split.read_verified(&OpenArchives).unwrap().as_ref(), b"def");
+    }
+
+    #[test]
+    fn test_verify_hash() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry {
+                preload: b"pre-",
+                ..TestEntry::archive("vtf", "materials", "b", 0, 0, 3)
+            },
+        ];
+        let (vpk, _dir_file) = read_with_archives("verify_hash", &entries, &[b"def"]);
+
+        let a = vpk.get(&Ext::Vmt, "materials", "a").unwrap();
+        assert!(a
+            .verify_hash(md5::compute(b"abc").0, &OpenArchives)
+            .unwrap());
+        assert!(!a.verify_hash([0; 16], &OpenArchives).unwrap());
+
+        let b = vpk.get(&Ext::Vtf, "materials", "b").unwrap();
+        assert!(b
+            .verify_hash(md5::compute(b"pre-def").0, &OpenArchives)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_copy_to_verified() {
+        let entries = [
+            TestEntry {
+                crc32: crc32fast::hash(b"pre-def"),
+                preload: b"pre-",
+                ..TestEntry::archive("vtf", "materials", "good", 0, 1, 3)
+            },
+            TestEntry {
+                crc32: 1234,
+                ..TestEntry::archive("vtf", "materials", "bad", 0, 1, 3)
+            },
+            TestEntry::archive("vtf", "materials", "truncated", 0, 1, 10),
+        ];
+        let (vpk, _dir_file) = read_with_archives("copy_to_verified", &entries, &[b".def"]);
+
+        let good = vpk.get(&Ext::Vtf, "materials", "good").unwrap();
+        let mut out = Vec::new();
+        assert_eq!(
+            good.copy_to_verified(&OpenArchives, &mut out).unwrap(),
+            (7, true)
+        );
+        assert_eq!(out, b"pre-def");
+
+        let bad = vpk.get(&Ext::Vtf, "materials", "bad").unwrap();
+        let mut out = Vec::new();
+        assert_eq!(
+            bad.copy_to_verified(&OpenArchives, &mut out).unwrap(),
+            (3, false)
+        );
+        assert_eq!(out, b"def");
+
+        let truncated = vpk.get(&Ext::Vtf, "materials", "truncated").unwrap();
+        assert!(truncated.copy_to(&OpenArchives, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_dir_entry_validate() {
+        let valid = VPKDirectoryEntry {
+            crc32: 0,
+            preload_length: 0,
+            archive_index: 0,
+            archive_offset: u32::MAX - 4,
+            file_length: 4,
+            suffix: 0xffff,
+        };
+        assert!(valid.validate().is_ok());
+        let bad_suffix = VPKDirectoryEntry { suffix: 0, ..valid };
+        assert!(matches!(
+            bad_suffix.validate(),
+            Err(Error::InvalidEntry { .. })
+        ));
+        let overflowing = VPKDirectoryEntry {
+            file_length: 5,
+            ..valid
+        };
+        assert!(matches!(
+            overflowing.validate(),
+            Err(Error::InvalidEntry { .. })
+        ));
+
+        // The parser uses the same checks
+        let entries = [TestEntry::archive("vtf", "materials", "a", 0, u32::MAX, 1)];
+        let data = build_vpk_v1(&entries, b"");
+        let err = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap_err();
+        match err {
+            Error::MalformedIndex { position } => {
+                assert_eq!(position, 12 + b"vtf\0materials\0a\0".len());
+            }
+            err => panic!("unexpected error: {err}"),
+        }
+
+        // Entries in the dir file also have to fit in it, with the offset relative to the tree
+        let inline = VPKDirectoryEntry {
+            archive_index: 0x7fff,
+            archive_offset: 2,
+            file_length: 3,
+            ..valid
+        };
+        assert!(inline.validate_inline(5).is_ok());
+        assert!(matches!(
+            inline.validate_inline(4),
+            Err(Error::InvalidEntry { .. })
+        ));
+        assert!(valid.validate_inline(0).is_ok());
+        let empty = VPKDirectoryEntry {
+            file_length: 0,
+            ..inline
+        };
+        assert!(empty.validate_inline(0).is_ok());
+
+        let entries = [TestEntry::archive("vtf", "materials", "a", 0x7fff, 2, 3)];
+        let read = |embed: &[u8]| {
+            let data = build_vpk_v1(&entries, embed);
+            VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None)
+        };
+        assert!(read(b"..xyz").is_ok());
+        assert!(matches!(
+            read(b"..xy"),
+            Err(Error::InlineDataOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_same_data_as() {
+        let entries = [
+            TestEntry {
+                crc32: crc32fast::hash(b"abc"),
+                ..TestEntry::preload("vmt", "materials", "a", b"abc")
+            },
+            TestEntry {
+                crc32: crc32fast::hash(b"abc"),
+                preload: b"a",
+                ..TestEntry::archive("vmt", "materials", "b", 0, 0, 2)
+            },
+            // Same CRC and size as `a`, but different data
+            TestEntry {
+                crc32: crc32fast::hash(b"abc"),
+                ..TestEntry::preload("vmt", "materials", "c", b"xyz")
+            },
+            TestEntry::preload("vmt", "materials", "d", b"abcd"),
+        ];
+        let (vpk, _dir_file) = read_with_archives("same_data_as", &entries, &[b"bc"]);
+
+        let entry = |name| vpk.get(&Ext::Vmt, "materials", name).unwrap().entry;
+        let same = |a, b| {
+            entry(a)
+                .same_data_as(&vpk, entry(b), &vpk, &OpenArchives)
+                .unwrap()
+        };
+        assert!(same("a", "b"));
+        assert!(!same("a", "c"));
+        assert!(!same("a", "d"));
+    }
+
+    #[test]
+    fn test_lazy_archives() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "a", 0, 0, 3),
+            TestEntry::archive("vtf", "materials", "b", 0, 3, 2),
+            TestEntry::archive("vtf", "materials", "c", 1, 0, 1),
+        ];
+        let (vpk, _dir_file) = read_with_archives("lazy_archives", &entries, &[b"abcde"]);
+
+        // The second archive doesn't exist, but that's only an error once we read from it
+        let archives = vpk.lazy_archives();
+        let a = vpk.get(&Ext::Vtf, "materials", "a").unwrap();
+        let b = vpk.get(&Ext::Vtf, "materials", "b").unwrap();
+        assert_eq!(a.get_with_files(&archives).unwrap().as_ref(), b"abc");
+        assert_eq!(b.get_with_files(&archives).unwrap().as_ref(), b"de");
+
+        let c = vpk.get(&Ext::Vtf, "materials", "c").unwrap();
+        assert!(c.get_with_files(&archives).is_err());
+    }
+
+    #[test]
+    fn test_load_entry() {
+        let entries = [TestEntry::preload("vmt", "materials", "brick", b"abc")];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let loaded = vpk
+            .get(&Ext::Vmt, "materials", "brick")
+            .unwrap()
+            .load(&OpenArchives)
+            .unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(&*loaded, b"abc");
+        assert_eq!(loaded.into_inner().as_ref(), b"abc");
+    }
+
+    #[test]
+    fn test_into_cow_static() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::archive("vtf", "materials", "b", 0, 1, 2),
+        ];
+        let mut collected: Vec<Cow<'static, [u8]>> = Vec::new();
+        {
+            let (vpk, _dir_file) = read_with_archives("into_cow_static", &entries, &[b".de"]);
+            let a = vpk.get(&Ext::Vmt, "materials", "a").unwrap();
+            assert!(matches!(a.get().unwrap(), Cow::Borrowed(_)));
+            collected.push(a.into_cow_static(&OpenArchives).unwrap());
+
+            let b = vpk.get(&Ext::Vtf, "materials", "b").unwrap();
+            collected.push(b.entry.get_static(&vpk, &OpenArchives).unwrap());
+        }
+
+        assert_eq!(collected, [b"abc".as_slice(), b"de"]);
+    }
+
+    #[test]
+    fn test_handle_identity() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::preload("vtf", "materials", "a", b"def"),
+            TestEntry::preload("vmt", "materials", "b", b"ghi"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let selected = vpk.get(&Ext::Vtf, "materials", "a").unwrap();
+        let matching: Vec<_> = vpk.iter().filter(|handle| *handle == selected).collect();
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].get().unwrap().as_ref(), b"def");
+        assert_ne!(selected, vpk.get(&Ext::Vmt, "materials", "a").unwrap());
+
+        let ids: std::collections::HashSet<_> = vpk.iter().map(|handle| handle.id()).collect();
+        assert_eq!(ids.len(), 3);
+
+        // Clones share the data, while reading it again doesn't
+        let cloned = vpk.clone();
+        assert_eq!(selected, cloned.get(&Ext::Vtf, "materials", "a").unwrap());
+        let reread = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        let other = reread.get(&Ext::Vtf, "materials", "a").unwrap();
+        assert_eq!(selected.id(), other.id());
+        assert_ne!(selected, other);
+    }
+
+    #[test]
+    fn test_save_to() {
+        let entries = [TestEntry {
+            preload: b"pre-",
+            ..TestEntry::archive("vtf", "materials", "brick", 0, 1, 3)
+        }];
+        let (vpk, _dir_file) = read_with_archives("save_to", &entries, &[b".def"]);
+
+        let out_dir = temp_dir("save_to_out");
+        let out = out_dir.join("nested/brick.vtf");
+        let brick = vpk.get(&Ext::Vtf, "materials", "brick").unwrap();
+        assert_eq!(brick.save_to(&out, &OpenArchives).unwrap(), 7);
+        assert_eq!(std::fs::read(&out).unwrap(), b"pre-def");
+    }
+
+    #[test]
+    fn test_counting_provider() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "a", 0, 0, 3),
+            TestEntry::archive("vtf", "materials", "b", 0, 3, 2),
+            TestEntry::archive("vtf", "materials", "c", 1, 0, 1),
+            TestEntry::preload("vmt", "materials", "d", b"abc"),
+        ];
+        let (vpk, _dir_file) = read_with_archives("counting_provider", &entries, &[b"abcde", b"f"]);
+
+        let prov = CountingProvider::new(vpk.lazy_archives());
+        for handle in vpk.iter() {
+            handle.get_with_files(&prov).unwrap();
+        }
+
+        let stats = prov.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[&0], ArchiveReadStats { reads: 2, bytes: 5 });
+        assert_eq!(stats[&1], ArchiveReadStats { reads: 1, bytes: 1 });
+
+        prov.reset_stats();
+        assert!(prov.stats().is_empty());
+    }
+
+    #[test]
+    fn test_handle_filename_with_ext() {
+        let entries = [
+            TestEntry::preload("vmt", "materials/concrete", "ConcreteFloor001a", b""),
+            TestEntry::preload("txt", "scripts", "items", b""),
+            TestEntry::preload(" ", " ", "readme", b""),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let handle = vpk
+            .get(&Ext::Vmt, "materials/concrete", "concretefloor001a")
+            .unwrap();
+        assert_eq!(handle.ext, Ext::Vmt);
+        assert_eq!(handle.ext_str(), "vmt");
+        assert_eq!(handle.filename_with_ext(), "ConcreteFloor001a.vmt");
+
+        let mut names: Vec<_> = vpk.iter().map(|h| h.filename_with_ext()).collect();
+        names.sort();
+        assert_eq!(names, ["ConcreteFloor001a.vmt", "items.txt", "readme"]);
+    }
+
+    #[test]
+    fn test_preload_bytes() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "inline", b"abc"),
+            TestEntry {
+                preload: b"VTF\0",
+                ..TestEntry::archive("vtf", "materials", "split", 0, 0, 100)
+            },
+            TestEntry::archive("vtf", "materials", "archived", 0, 0, 100),
+        ];
+        // No archive file, since preload_bytes shouldn't touch it
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let preload = |ext, name| vpk.get(ext, "materials", name).unwrap().preload_bytes();
+        assert_eq!(preload(&Ext::Vmt, "inline"), b"abc");
+        assert_eq!(preload(&Ext::Vtf, "split"), b"VTF\0");
+        assert_eq!(preload(&Ext::Vtf, "archived"), b"");
+    }
+
+    #[test]
+    fn test_handle_display() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "x", b"abc"),
+            TestEntry::archive("vtf", "materials", "x", 0, 0, 1229),
+            TestEntry::archive("mdl", "models", "big", 2, 0, 5 * 1024 * 1024),
+            TestEntry::preload(" ", " ", "readme", b""),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let display = |ext, dir, name| vpk.get(ext, dir, name).unwrap().to_string();
+        assert_eq!(
+            display(&Ext::Vmt, "materials", "x"),
+            "materials/x.vmt (3 B, inline)"
+        );
+        assert_eq!(
+            display(&Ext::Vtf, "materials", "x"),
+            "materials/x.vtf (1.2 KiB, archive 0)"
+        );
+        assert_eq!(
+            display(&Ext::Mdl, "models", "big"),
+            "models/big.mdl (5.0 MiB, archive 2)"
+        );
+        let readme = Ext::from(" ");
+        assert_eq!(display(&readme, " ", "readme"), "readme (0 B, inline)");
+    }
+
+    #[test]
+    fn test_fallback_provider() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "base", 0, 0, 4),
+            TestEntry::archive("vtf", "materials", "patched", 1, 0, 5),
+            TestEntry::archive("vtf", "materials", "missing", 2, 0, 1),
+        ];
+        let (vpk, _dir_file) = read_with_archives("fallback_provider", &entries, &[]);
+        let base_dir = temp_dir("fallback_provider_base");
+        let patch_dir = temp_dir("fallback_provider_patch");
+        std::fs::write(base_dir.join("pak01_000.vpk"), b"base").unwrap();
+        std::fs::write(base_dir.join("pak01_001.vpk"), b"stale").unwrap();
+        std::fs::write(patch_dir.join("pak01_001.vpk"), b"patch").unwrap();
+
+        let prov = FallbackProvider::new(vec![
+            LazyArchives::in_dir(&vpk, &*patch_dir),
+            LazyArchives::in_dir(&vpk, &*base_dir),
+        ]);
+        let read = |name| {
+            vpk.get(&Ext::Vtf, "materials", name)
+                .unwrap()
+                .get_with_files(&prov)
+        };
+        assert_eq!(read("base").unwrap().as_ref(), b"base");
+        assert_eq!(read("patched").unwrap().as_ref(), b"patch");
+        // None of the providers have it, so it falls back to the archive path next to the dir
+        assert!(read("missing").is_err());
+    }
+
+    #[test]
+    fn test_missing_archive_index() {
+        let entries = [TestEntry::archive("vtf", "materials", "a", 0, 0, 4)];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        let mut entry = vpk.get(&Ext::Vtf, "materials", "a").unwrap().entry.clone();
+        entry.dir_entry.archive_index = 5;
+
+        let not_found = |err: Option<std::io::Error>| {
+            err.is_some_and(|err| err.kind() == std::io::ErrorKind::NotFound)
+        };
+        assert!(not_found(entry.get(&vpk).err()));
+        assert!(not_found(
+            entry.get_with_files(&vpk, &vpk.lazy_archives()).err()
+        ));
+        assert!(not_found(
+            entry.copy_to(&vpk, &Vec::new(), &mut std::io::sink()).err()
+        ));
+        assert!(not_found(vpk.lazy_archives().vpk_reader(5).err()));
+    }
+
+    #[test]
+    fn test_archive_file_exists() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "present", 0, 0, 1),
+            TestEntry::archive("vtf", "materials", "missing", 1, 0, 1),
+            TestEntry::preload("vmt", "materials", "inline", b"abc"),
+        ];
+        let (vpk, _dir_file) = read_with_archives("archive_file_exists", &entries, &[b"a"]);
+
+        let exists = |name| {
+            vpk.get(&Ext::Vtf, "materials", name)
+                .or_else(|| vpk.get(&Ext::Vmt, "materials", name))
+                .unwrap()
+                .archive_file_exists()
+        };
+        assert!(exists("present"));
+        assert!(!exists("missing"));
+        assert!(exists("inline"));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_decode_image() {
+        let image = image::RgbImage::from_fn(2, 1, |x, _| image::Rgb([x as u8 * 255, 0, 0]));
+        let mut bmp = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bmp), image::ImageFormat::Bmp)
+            .unwrap();
+        let bmp: &'static [u8] = Box::leak(bmp.into_boxed_slice());
+
+        let entries = [
+            TestEntry::preload("bmp", "materials", "red", bmp),
+            TestEntry::preload("vtf", "materials", "red", b"VTF"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let red = vpk.get(&Ext::Bmp, "materials", "red").unwrap();
+        let decoded = red.decode_image(&Vec::new()).unwrap().into_rgb8();
+        assert_eq!(decoded, image);
+
+        let vtf = vpk.get(&Ext::Vtf, "materials", "red").unwrap();
+        let err = vtf.decode_image(&Vec::new()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Image(image::ImageError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_entry_reader() {
+        let entries = [
+            TestEntry {
+                preload: b"pre-",
+                ..TestEntry::archive("vtf", "materials", "a", 0, 1, 3)
+            },
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+        ];
+        let (vpk, _dir_file) = read_with_archives("entry_reader", &entries, &[b".def."]);
+
+        let vtf = vpk.get(&Ext::Vtf, "materials", "a").unwrap();
+        let mut reader = vtf.to_reader().unwrap();
+        assert_eq!(reader.len(), 7);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"pre-def");
+
+        // Seeking within the archive data, and back into the preload data
+        reader.seek(SeekFrom::Start(5)).unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"ef");
+        reader.seek(SeekFrom::End(-4)).unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"-def");
+        assert!(reader.seek(SeekFrom::Current(-10)).is_err());
+
+        let files = vpk.open_all_archive_paths().unwrap();
+        let mut reader = vtf.to_reader_with_file(files[0].as_ref().unwrap()).unwrap();
+        let mut data = String::new();
+        reader.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "pre-def");
+
+        let vmt = vpk.get(&Ext::Vmt, "materials", "a").unwrap();
+        let mut data = Vec::new();
+        vmt.to_reader().unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"abc");
+    }
+
+    #[test]
+    fn test_lazy_archives_open_count() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "a", 0, 0, 3),
+            TestEntry::archive("vtf", "materials", "b", 1, 0, 3),
+        ];
+        let (vpk, _dir_file) =
+            read_with_archives("lazy_archives_open_count", &entries, &[b"abc", b"def"]);
+        let a = vpk.get(&Ext::Vtf, "materials", "a").unwrap();
+        let b = vpk.get(&Ext::Vtf, "materials", "b").unwrap();
+
+        // Files are only opened once they're read from, and only once
+        let mut lazy = vpk.lazy_archives();
+        assert_eq!(lazy.open_count(), 0);
+        assert_eq!(a.get_with_files(&lazy).unwrap().as_ref(), b"abc");
+        assert_eq!(lazy.open_count(), 1);
+        assert_eq!(a.get_with_files(&lazy).unwrap().as_ref(), b"abc");
+        assert_eq!(b.get_with_files(&lazy).unwrap().as_ref(), b"def");
+        assert_eq!(lazy.open_count(), 2);
+
+        lazy.close_all();
+        assert_eq!(lazy.open_count(), 0);
+        // They're opened again when read from after being closed
+        assert_eq!(a.get_with_files(&lazy).unwrap().as_ref(), b"abc");
+        assert_eq!(lazy.open_count(), 1);
+    }
+
+    #[test]
+    fn test_inline_embed_data() {
+        let entries = [
+            TestEntry {
+                preload: b"pre",
+                ..TestEntry::archive("vtf", "materials", "a", 0x7fff, 0, 4)
+            },
+            TestEntry::archive("vtf", "materials", "b", 0x7fff, 4, 2),
+            TestEntry {
+                preload: b"pre",
+                ..TestEntry::archive("vtf", "materials", "c", 0x7fff, 6, 0)
+            },
+        ];
+        let data = build_vpk_v2(&entries, b"dataxy");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        for (name, expected) in [("a", b"predata".as_slice()), ("b", b"xy"), ("c", b"pre")] {
+            let handle = vpk.get(&Ext::Vtf, "materials", name).unwrap();
+            assert_eq!(handle.entry.total_size(), expected.len() as u64);
+            assert_eq!(handle.get().unwrap().as_ref(), expected);
+
+            let mut out = Vec::new();
+            handle.copy_to(&OpenArchives, &mut out).unwrap();
+            assert_eq!(out, expected);
+
+            let mut reader = handle.to_reader().unwrap();
+            assert_eq!(reader.len(), expected.len() as u64);
+            reader.seek(SeekFrom::Start(2)).unwrap();
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).unwrap();
+            assert_eq!(rest, &expected[2..]);
+        }
+
+        let a = vpk.get(&Ext::Vtf, "materials", "a").unwrap();
+        assert!(matches!(a.get().unwrap(), Cow::Owned(_)));
+        let b = vpk.get(&Ext::Vtf, "materials", "b").unwrap();
+        assert!(matches!(b.get().unwrap(), Cow::Borrowed(_)));
+
+        let entries = [TestEntry::archive("vtf", "materials", "a", 0x7fff, 100, 4)];
+        let data = build_vpk_v2(&entries, b"data");
+        let err = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap_err();
+        assert!(matches!(err, Error::InlineDataOutOfBounds { .. }));
+    }
+
+    #[test]
+    fn test_archive_relative_path() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "a", 7, 0, 1),
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+        ];
+        let (vpk, path) = read_with_archives("archive_relative_path", &entries, &[]);
+
+        let vtf = vpk.get(&Ext::Vtf, "materials", "a").unwrap();
+        assert_eq!(
+            vtf.archive_path(),
+            path.with_file_name("pak01_007.vpk").to_str()
+        );
+        assert_eq!(vtf.archive_relative_path(), Some("pak01_007.vpk"));
+        let vmt = vpk.get(&Ext::Vmt, "materials", "a").unwrap();
+        assert_eq!(vmt.archive_relative_path(), None);
+    }
+
+    #[test]
+    fn test_read_to_string() {
+        let entries = [
+            TestEntry::preload(
+                "vmt",
+                "materials",
+                "utf8",
+                "\"LightmappedGeneric\" // ü".as_bytes(),
+            ),
+            // "café" in Windows-1252
+            TestEntry::preload("txt", "scripts", "cp1252", b"caf\xe9"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        let prov = Vec::new();
+
+        let utf8 = vpk.get(&Ext::Vmt, "materials", "utf8").unwrap();
+        assert_eq!(
+            utf8.read_to_string(&prov).unwrap(),
+            "\"LightmappedGeneric\" // ü"
+        );
+
+        let txt = Ext::from_ext_slice(b"txt");
+        let cp1252 = vpk.get(&txt, "scripts", "cp1252").unwrap();
+        let err = cp1252.read_to_string(&prov).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidUtf8 { path, error } if path == "scripts/cp1252.txt" && error.valid_up_to() == 3
+        ));
+        assert_eq!(cp1252.read_to_string_lossy(&prov).unwrap(), "caf\u{fffd}");
+        #[cfg(feature = "encoding_rs")]
+        assert_eq!(cp1252.read_to_string_cp1252(&prov).unwrap(), "café");
+    }
+
+    #[test]
+    fn test_raw_dir_entry_bytes() {
+        let entries = [
+            TestEntry {
+                crc32: 0x1234_5678,
+                preload: b"abc",
+                ..TestEntry::archive("vtf", "materials", "a", 3, 0x10, 0x20)
+            },
+            TestEntry::preload("vmt", "materials", "b", b""),
+        ];
+
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let a = vpk.get(&Ext::Vtf, "materials", "a").unwrap();
+        let mut expected = Vec::new();
+        a.entry.dir_entry.write_le(&mut expected).unwrap();
+        assert_eq!(a.entry.raw_dir_entry_bytes(&vpk), expected);
+
+        // The archive offset of inline entries is adjusted while reading, so only the raw bytes
+        // still have the offset as stored
+        let b = vpk.get(&Ext::Vmt, "materials", "b").unwrap();
+        let raw = b.entry.raw_dir_entry_bytes(&vpk);
+        assert_eq!(raw.len(), VPKDirectoryEntry::SIZE);
+        let dir_entry = VPKDirectoryEntry::read_le(&mut Cursor::new(raw)).unwrap();
+        assert_eq!(dir_entry.archive_offset, 0);
+        assert_ne!(b.entry.dir_entry.archive_offset, 0);
+        assert_eq!(dir_entry.archive_index, 0x7fff);
+    }
+
+    #[test]
+    fn test_relative_to() {
+        let entries = [
+            TestEntry::preload("vmt", "Materials/Concrete", "floor001a", b""),
+            TestEntry::preload("vmt", "materials", "top", b""),
+            TestEntry::preload("vmt", "materials_extra", "a", b""),
+            TestEntry::preload("txt", " ", "readme", b""),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let floor = vpk
+            .get(&Ext::Vmt, "materials/concrete", "floor001a")
+            .unwrap();
+        assert_eq!(
+            floor.relative_to("materials/").as_deref(),
+            Some("Concrete/floor001a.vmt")
+        );
+        assert_eq!(
+            floor.relative_to("MATERIALS\\concrete").as_deref(),
+            Some("floor001a.vmt")
+        );
+        assert_eq!(
+            floor.relative_to("").as_deref(),
+            Some("Materials/Concrete/floor001a.vmt")
+        );
+        assert_eq!(floor.relative_to("models"), None);
+        assert_eq!(floor.relative_to("materials/conc"), None);
+
+        let top = vpk.get(&Ext::Vmt, "materials", "top").unwrap();
+        assert_eq!(top.relative_to("materials").as_deref(), Some("top.vmt"));
+        let extra = vpk.get(&Ext::Vmt, "materials_extra", "a").unwrap();
+        assert_eq!(extra.relative_to("materials"), None);
+
+        let txt = Ext::from_ext_slice(b"txt");
+        let readme = vpk.get(&txt, " ", "readme").unwrap();
+        assert_eq!(readme.relative_to("/").as_deref(), Some("readme.txt"));
+        assert_eq!(readme.relative_to("materials"), None);
     }
 }
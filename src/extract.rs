@@ -0,0 +1,296 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::access::DirFile;
+use crate::entry::{VPKEntry, VpkReaderProvider};
+use crate::vpk::Ext;
+use crate::{Error, VPK};
+
+/// Options for [`VPK::extract_all_with_options`]
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    /// Set the modified time of every extracted file to that of the dir file, so that extracting
+    /// the same VPK always gives the same times. See [`VPK::source_modified`].
+    pub set_modified: bool,
+    /// Don't create files for entries with no data at all. See [`VPKEntry::is_empty`].
+    pub skip_empty: bool,
+}
+
+impl<S> VPK<S> {
+    /// Extract every entry in the VPK into `out_dir`, keeping the directory structure.
+    pub fn extract_all(&self, out_dir: impl AsRef<Path>) -> Result<(), Error> {
+        self.extract_all_with_options(out_dir, &ExtractOptions::default())
+    }
+
+    /// Extract every entry in the VPK into `out_dir`, keeping the directory structure.
+    pub fn extract_all_with_options(
+        &self,
+        out_dir: impl AsRef<Path>,
+        options: &ExtractOptions,
+    ) -> Result<(), Error> {
+        let out_dir = out_dir.as_ref();
+        let modified = if options.set_modified {
+            Some(self.source_modified()?)
+        } else {
+            None
+        };
+
+        let files = self.open_all_archive_paths()?;
+        for (ext, map) in self.tree.maps() {
+            for (dir_file, entry) in map {
+                if options.skip_empty && entry.is_empty() {
+                    continue;
+                }
+
+                self.extract_entry(out_dir, &ext, dir_file, entry, &files, modified)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Extract every entry in the VPK into `out_dir` using `num_threads` threads, keeping the
+    /// directory structure.  
+    /// Each thread opens its own handles to the archive files.
+    #[cfg(feature = "rayon")]
+    pub fn extract_all_parallel(
+        &self,
+        out_dir: impl AsRef<Path>,
+        num_threads: usize,
+    ) -> Result<(), Error>
+    where
+        S: Sync,
+    {
+        self.extract_all_parallel_with_options(out_dir, num_threads, &ExtractOptions::default())
+    }
+
+    /// Like [`VPK::extract_all_parallel`], with the same options as
+    /// [`VPK::extract_all_with_options`].
+    #[cfg(feature = "rayon")]
+    pub fn extract_all_parallel_with_options(
+        &self,
+        out_dir: impl AsRef<Path>,
+        num_threads: usize,
+        options: &ExtractOptions,
+    ) -> Result<(), Error>
+    where
+        S: Sync,
+    {
+        use rayon::prelude::*;
+
+        let out_dir = out_dir.as_ref();
+        let modified = if options.set_modified {
+            Some(self.source_modified()?)
+        } else {
+            None
+        };
+        let entries: Vec<_> = self
+            .tree
+            .iter_with_ext()
+            .filter(|(_, _, entry)| !(options.skip_empty && entry.is_empty()))
+            .collect();
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .map_err(std::io::Error::other)?;
+
+        pool.install(|| {
+            entries.par_iter().try_for_each_init(
+                || self.open_all_archive_paths(),
+                |files, (ext, dir_file, entry)| {
+                    let files = match files {
+                        Ok(files) => &*files,
+                        // The error can't be moved out since it's kept for the thread
+                        Err(err) => {
+                            let err = std::io::Error::new(err.kind(), err.to_string());
+                            return Err(err.into());
+                        }
+                    };
+                    self.extract_entry(out_dir, ext, dir_file, entry, files, modified)
+                },
+            )
+        })
+    }
+
+    fn extract_entry(
+        &self,
+        out_dir: &Path,
+        ext: &Ext<'_>,
+        dir_file: &DirFile,
+        entry: &VPKEntry,
+        prov: &impl VpkReaderProvider,
+        modified: Option<SystemTime>,
+    ) -> Result<(), Error> {
+        let path = out_dir.join(entry_path(ext, dir_file)?);
+        if let Some(parent) = path.parent() {
+            // This is fine to race with other threads, it doesn't fail if the directory already
+            // exists
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut file = BufWriter::new(File::create(&path)?);
+        entry.copy_to(self, prov, &mut file)?;
+        file.flush()?;
+
+        if let Some(modified) = modified {
+            file.get_ref().set_modified(modified)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the relative path of an entry, like `materials/concrete/concretefloor001a.vmt`.  
+/// VPKs store an empty extension as a single space.  
+/// Fails if the path has `..` components or is absolute, since the dir comes from the VPK and
+/// could otherwise point anywhere.
+fn entry_path(ext: &Ext<'_>, dir_file: &DirFile) -> Result<PathBuf, Error> {
+    // Some VPKs use `\` as the separator, which isn't one on most platforms
+    let dir = String::from_utf8_lossy(dir_file.dir_or_empty()).replace('\\', "/");
+    let filename = String::from_utf8_lossy(dir_file.filename());
+    let ext = String::from_utf8_lossy(ext.as_slice());
+
+    let mut path = PathBuf::new();
+    if !dir.is_empty() {
+        path.push(&dir);
+    }
+    if ext == " " {
+        path.push(filename.as_ref());
+    } else {
+        path.push(format!("{}.{}", filename, ext));
+    }
+
+    let is_unsafe = path.components().any(|component| {
+        matches!(
+            component,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    });
+    if is_unsafe {
+        return Err(Error::UnsafePath {
+            path: path.display().to_string(),
+        });
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExtractOptions;
+    use crate::vpk::tests::{build_vpk_v1, read_with_archives, temp_dir, TempFile, TestEntry};
+    use crate::vpk::ProbableKind;
+    use crate::{Error, VPK};
+
+    /// The VPK, and its dir file, which has to be kept around for the archive file next to it
+    fn extract_vpk(test_name: &str) -> (VPK, TempFile) {
+        let entries = [
+            TestEntry::preload("vmt", "materials/brick", "a", b"abc"),
+            TestEntry::preload(" ", " ", "readme", b"hi"),
+            TestEntry {
+                preload: b"pre-",
+                ..TestEntry::archive("vtf", "materials/brick", "a", 0, 1, 3)
+            },
+            TestEntry::preload("txt", "empty", "placeholder", b""),
+        ];
+        read_with_archives(test_name, &entries, &[b".def"])
+    }
+
+    #[track_caller]
+    fn assert_extracted(out_dir: &std::path::Path) {
+        let read = |path: &str| std::fs::read(out_dir.join(path)).unwrap();
+        assert_eq!(read("materials/brick/a.vmt"), b"abc");
+        assert_eq!(read("materials/brick/a.vtf"), b"pre-def");
+        assert_eq!(read("readme"), b"hi");
+    }
+
+    #[test]
+    fn test_extract_skip_empty() {
+        let (vpk, _dir_file) = extract_vpk("extract_skip_empty");
+        let out_dir = temp_dir("extract_skip_empty_out");
+        vpk.extract_all(&out_dir).unwrap();
+        assert_eq!(
+            std::fs::read(out_dir.join("empty/placeholder.txt")).unwrap(),
+            b""
+        );
+
+        let out_dir = temp_dir("extract_skip_empty_out_skipped");
+        let options = ExtractOptions {
+            skip_empty: true,
+            ..ExtractOptions::default()
+        };
+        vpk.extract_all_with_options(&out_dir, &options).unwrap();
+        assert_extracted(&out_dir);
+        assert!(!out_dir.join("empty").exists());
+    }
+
+    #[test]
+    fn test_extract_all() {
+        let (vpk, _dir_file) = extract_vpk("extract_all");
+        let out_dir = temp_dir("extract_all_out");
+        vpk.extract_all(&out_dir).unwrap();
+
+        assert_extracted(&out_dir);
+    }
+
+    #[test]
+    fn test_extract_set_modified() {
+        let (vpk, _dir_file) = extract_vpk("extract_set_modified");
+        let out_dir = temp_dir("extract_set_modified_out");
+        let options = ExtractOptions {
+            set_modified: true,
+            ..ExtractOptions::default()
+        };
+        vpk.extract_all_with_options(&out_dir, &options).unwrap();
+
+        assert_extracted(&out_dir);
+        let modified = std::fs::metadata(out_dir.join("materials/brick/a.vtf"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert_eq!(modified, vpk.source_modified().unwrap());
+    }
+
+    #[test]
+    fn test_extract_unsafe_path() {
+        let out_dir = temp_dir("extract_unsafe_path_out");
+        for dir in ["../../escaped", "/tmp/escaped", "materials/../../escaped"] {
+            let entries = [TestEntry::preload("txt", dir, "a", b"abc")];
+            let data = build_vpk_v1(&entries, b"");
+            let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+            let err = vpk.extract_all(out_dir.join("out")).unwrap_err();
+            assert!(matches!(err, Error::UnsafePath { .. }), "{dir}: {err:?}");
+        }
+        assert!(!out_dir.join("escaped").exists());
+        assert!(!out_dir.parent().unwrap().join("escaped").exists());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_extract_all_parallel() {
+        let (vpk, _dir_file) = extract_vpk("extract_all_parallel");
+        let out_dir = temp_dir("extract_all_parallel_out");
+        vpk.extract_all_parallel(&out_dir, 2).unwrap();
+
+        assert_extracted(&out_dir);
+
+        let out_dir = temp_dir("extract_all_parallel_out_options");
+        let options = ExtractOptions {
+            set_modified: true,
+            skip_empty: true,
+        };
+        vpk.extract_all_parallel_with_options(&out_dir, 2, &options)
+            .unwrap();
+        assert_extracted(&out_dir);
+        assert!(!out_dir.join("empty").exists());
+        let modified = std::fs::metadata(out_dir.join("readme"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert_eq!(modified, vpk.source_modified().unwrap());
+    }
+}
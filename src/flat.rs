@@ -0,0 +1,150 @@
+use std::hash::{Hash, Hasher};
+
+use indexmap::{Equivalent, IndexMap};
+
+use crate::access::{DirFile, DirFileRefLowercase, MapRandomState};
+use crate::entry::{VPKEntry, VPKEntryHandle};
+use crate::vpk::{Ext, VPKTree};
+use crate::VPK;
+
+/// (Ext, Dir, Filename) -> VPKEntry
+pub type FlatTreeMap = IndexMap<(Ext<'static>, DirFile), VPKEntry, MapRandomState>;
+
+/// Every entry of a VPK in a single map, rather than a map per extension like [`VPKTree`].
+/// Lookups are a bit slower, since the extension is part of the key, but it is much simpler to
+/// iterate over or process generically. See [`VPK::flat_tree`].
+#[derive(Debug, Default, Clone)]
+pub struct FlatTree {
+    map: FlatTreeMap,
+}
+impl FlatTree {
+    pub fn map(&self) -> &FlatTreeMap {
+        &self.map
+    }
+
+    pub fn into_map(self) -> FlatTreeMap {
+        self.map
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Get the entry, comparing the dir and filename case-insensitively
+    pub fn get(&self, ext: &Ext<'_>, dir: &str, filename: &str) -> Option<&VPKEntry> {
+        self.get_full(ext, dir, filename).map(|(_, _, entry)| entry)
+    }
+
+    /// Like [`FlatTree::get`], but also gives the key the entry is stored under.
+    pub fn get_full(
+        &self,
+        ext: &Ext<'_>,
+        dir: &str,
+        filename: &str,
+    ) -> Option<(&Ext<'static>, &DirFile, &VPKEntry)> {
+        let key = FlatKeyRef {
+            ext,
+            dir_file: DirFileRefLowercase::new(dir, filename),
+        };
+        let ((ext, dir_file), entry) = self.map.get_key_value(&key)?;
+        Some((ext, dir_file, entry))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Ext<'static>, &DirFile, &VPKEntry)> {
+        self.map
+            .iter()
+            .map(|((ext, dir_file), entry)| (ext, dir_file, entry))
+    }
+}
+impl<S> From<&VPKTree<S>> for FlatTree {
+    fn from(tree: &VPKTree<S>) -> FlatTree {
+        let mut map = FlatTreeMap::default();
+        map.reserve(tree.iter().count());
+        for (ext, ext_map) in tree.maps() {
+            let ext = ext.into_owned();
+            for (dir_file, entry) in ext_map {
+                map.insert((ext.clone(), dir_file.clone()), entry.clone());
+            }
+        }
+
+        FlatTree { map }
+    }
+}
+
+impl<S> VPK<S> {
+    /// Copy every entry into a [`FlatTree`], a single map keyed by extension and dir/filename.
+    /// Reading the data of an entry still needs the [`VPK`], see [`VPK::flat_handle`].
+    pub fn flat_tree(&self) -> FlatTree {
+        FlatTree::from(&self.tree)
+    }
+
+    /// Get a handle to an entry from a [`FlatTree`] of this VPK, so that its data can be read.
+    pub fn flat_handle<'a>(
+        &'a self,
+        ext: &Ext<'a>,
+        dir_file: &'a DirFile,
+        entry: &'a VPKEntry,
+    ) -> VPKEntryHandle<'a, S> {
+        VPKEntryHandle {
+            vpk: self,
+            ext: ext.clone(),
+            dir_file,
+            entry,
+        }
+    }
+}
+
+/// Borrowed key for looking up in a [`FlatTreeMap`] without allocating
+struct FlatKeyRef<'a, 'b> {
+    ext: &'a Ext<'b>,
+    dir_file: DirFileRefLowercase<'a>,
+}
+impl Hash for FlatKeyRef<'_, '_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Must match the hash of `(Ext, DirFile)`
+        self.ext.hash(state);
+        self.dir_file.hash(state);
+    }
+}
+impl Equivalent<(Ext<'static>, DirFile)> for FlatKeyRef<'_, '_> {
+    fn equivalent(&self, (ext, dir_file): &(Ext<'static>, DirFile)) -> bool {
+        self.ext == ext && self.dir_file.equivalent(dir_file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vpk::tests::{build_vpk_v1, TestEntry};
+    use crate::vpk::{Ext, ProbableKind};
+    use crate::VPK;
+
+    #[test]
+    fn test_flat_tree() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "brick", b"abc"),
+            TestEntry::preload("vtf", "materials", "brick", b"def"),
+            TestEntry::preload("txt", "scripts", "Items", b"ghi"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let flat = vpk.flat_tree();
+        assert_eq!(flat.len(), 3);
+        assert_eq!(flat.iter().count(), 3);
+
+        let (ext, dir_file, brick) = flat.get_full(&Ext::Vtf, "materials", "brick").unwrap();
+        let handle = vpk.flat_handle(ext, dir_file, brick);
+        assert_eq!(handle.get().unwrap().as_ref(), b"def");
+
+        let txt = Ext::from_ext_slice(b"txt");
+        let (ext, dir_file, items) = flat.get_full(&txt, "Scripts", "items").unwrap();
+        let handle = vpk.flat_handle(ext, dir_file, items);
+        assert_eq!(handle.filename_with_ext(), "Items.txt");
+        assert_eq!(handle.get().unwrap().as_ref(), b"ghi");
+        assert!(flat.get(&Ext::Vmt, "scripts", "items").is_none());
+    }
+}
@@ -1,12 +1,18 @@
 pub mod access;
+pub mod cache;
 pub mod entry;
+pub mod extract;
+pub mod flat;
+pub mod manifest;
 mod parse;
+pub mod sorted;
 pub mod structs;
+pub mod vfs;
 pub mod vpk;
 
 pub use crate::vpk::VPK;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 use vpk::ProbableKind;
 
@@ -18,12 +24,89 @@ pub enum Error {
     ExpectedNullTerminator,
     #[error("Invalid signature, provided file is not a VPK file")]
     InvalidSignature,
+    #[error(
+        "Invalid signature, {path} looks like a numbered archive file, try {suggested} instead"
+    )]
+    NumberedArchive { path: String, suggested: String },
     #[error("Unsupported VPK version({0}), only version 2 and low")]
     UnsupportedVersion(u32),
     #[error("Mismatched size for hashes section")]
     HashSizeMismatch,
-    #[error("Malformed index encountered while parsing")]
-    MalformedIndex,
+    /// The entry at `position` in the dir file failed [`entry::VPKDirectoryEntry::validate`],
+    /// which gives the reason.
+    #[error("Malformed index at byte {position:#x} while parsing")]
+    MalformedIndex { position: usize },
+    /// From [`entry::VPKDirectoryEntry::validate`]
+    #[error("Invalid entry: {reason}")]
+    InvalidEntry { reason: &'static str },
+    /// Reading the tree failed at `position` in the dir file, usually because it ended early.
+    #[error("Error while reading the tree at byte {position:#x}: {error}")]
+    TreeReadError {
+        position: usize,
+        #[source]
+        error: std::io::Error,
+    },
+    #[error("Missing archive files: {0:?}")]
+    MissingArchives(Vec<String>),
+    #[error("VPK has no checksums, only version 2 VPKs have them")]
+    NoChecksums,
+    #[error("Preload data {preload:?} of {path} is outside of the VPK data")]
+    PreloadOutOfBounds {
+        path: String,
+        preload: std::ops::Range<usize>,
+    },
+    #[error("Inline data {inline:?} of {path} is outside of the VPK data")]
+    InlineDataOutOfBounds {
+        path: String,
+        inline: std::ops::Range<usize>,
+    },
+    #[error("The {region} region {start}..{end} is outside of the {len} bytes of VPK data")]
+    LayoutOutOfBounds {
+        region: &'static str,
+        start: u64,
+        end: u64,
+        len: usize,
+    },
+    #[error("{path} is not valid UTF-8: {error}")]
+    InvalidUtf8 {
+        path: String,
+        #[source]
+        error: std::str::Utf8Error,
+    },
+    /// [`vpk::ReadOptions::archive_dir`] isn't valid UTF-8, which the archive paths have to be.
+    #[error("Archive dir {0:?} is not valid UTF-8")]
+    NonUtf8ArchiveDir(PathBuf),
+    /// The VPK has more entries than [`vpk::ReadOptions::max_entries`] allows.
+    #[error("VPK has more than the maximum of {max} entries")]
+    TooManyEntries { max: usize },
+    /// The path of an entry would be outside of the directory it is extracted into, because it
+    /// has a `..` component or is absolute.
+    #[error("Entry path {path} is outside of the output directory")]
+    UnsafePath { path: String },
+    #[error("Duplicate entry {path}")]
+    DuplicateEntry { path: String },
+    #[error("Region {0:?} is outside of the VPK data")]
+    RegionOutOfBounds(std::ops::Range<usize>),
+    /// The data was read successfully, but its CRC32 did not match the one stored in the entry.
+    #[error("CRC32 mismatch, expected {expected:#010x} but got {actual:#010x}")]
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+        /// The data that was read
+        data: Vec<u8>,
+    },
+    #[cfg(feature = "image")]
+    #[error("Failed to decode image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("The directory tree is LZMA compressed, which needs the `lzma` feature")]
+    CompressedTree,
+    #[error("The directory tree does not start with an LZMA header")]
+    MissingLzmaHeader,
+    #[error("The compressed directory tree is {size} bytes, more than the maximum of {max}")]
+    TreeTooLarge { size: u32, max: u32 },
+    #[cfg(feature = "lzma")]
+    #[error("Failed to decompress the directory tree: {0}")]
+    Lzma(#[from] lzma_rs::error::Error),
 }
 
 pub fn from_path(path: impl AsRef<Path>, probable_kind: ProbableKind) -> Result<VPK, Error> {
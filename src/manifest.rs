@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use crate::access::normalize_path_byte;
+use crate::VPK;
+
+/// The result of [`VPK::verify_manifest`].  
+/// Each list is sorted by path.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ManifestReport {
+    /// Entries whose CRC32 matches the manifest
+    pub matched: Vec<String>,
+    /// Entries whose CRC32 is different from the one in the manifest
+    pub mismatched: Vec<ManifestMismatch>,
+    /// Paths in the manifest that aren't in the VPK, as they are written in the manifest
+    pub missing: Vec<String>,
+    /// Entries in the VPK that aren't in the manifest
+    pub extra: Vec<String>,
+}
+impl ManifestReport {
+    /// Whether the VPK has exactly the entries in the manifest, all with the expected CRC32s
+    pub fn is_ok(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// See [`ManifestReport::mismatched`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestMismatch {
+    pub path: String,
+    /// The CRC32 in the manifest
+    pub expected: u32,
+    /// The CRC32 stored in the entry
+    pub actual: u32,
+}
+
+impl<S> VPK<S> {
+    /// Compare the entries of the VPK against a manifest of paths, like
+    /// `materials/brick/wall01.vtf`, to their expected CRC32s.  
+    /// Paths are compared like [`VPK::get_ignore_case`], so casing and `\` separators don't
+    /// matter. Only the CRC32s stored in the dir file are compared, the data isn't read, so this
+    /// is cheap but doesn't catch archive files that were corrupted after the VPK was written. Use
+    /// [`VPKEntry::read_verified`](crate::entry::VPKEntry::read_verified) for that.
+    pub fn verify_manifest(&self, manifest: &HashMap<String, u32>) -> ManifestReport {
+        // Normalized path -> (the path in the manifest, expected crc, whether it was found)
+        let mut expected: HashMap<String, (&str, u32, bool)> = manifest
+            .iter()
+            .map(|(path, &crc32)| (normalize_path(path), (path.as_str(), crc32, false)))
+            .collect();
+
+        let mut report = ManifestReport::default();
+        for handle in self.iter() {
+            let path = handle.full_path();
+            let actual = handle.entry.dir_entry.crc32;
+            match expected.get_mut(&normalize_path(&path)) {
+                Some((_, crc32, found)) => {
+                    *found = true;
+                    if *crc32 == actual {
+                        report.matched.push(path);
+                    } else {
+                        report.mismatched.push(ManifestMismatch {
+                            path,
+                            expected: *crc32,
+                            actual,
+                        });
+                    }
+                }
+                None => report.extra.push(path),
+            }
+        }
+
+        report.missing = expected
+            .into_values()
+            .filter(|(_, _, found)| !found)
+            .map(|(path, _, _)| path.to_string())
+            .collect();
+
+        report.matched.sort_unstable();
+        report
+            .mismatched
+            .sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        report.missing.sort_unstable();
+        report.extra.sort_unstable();
+
+        report
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    let path: Vec<u8> = path.bytes().map(normalize_path_byte).collect();
+    // Only ASCII bytes are changed, so this is still valid UTF-8
+    String::from_utf8(path).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::ManifestMismatch;
+    use crate::vpk::tests::{build_vpk_v1, TestEntry};
+    use crate::vpk::ProbableKind;
+    use crate::VPK;
+
+    #[test]
+    fn test_verify_manifest() {
+        let crc = |crc32, entry: TestEntry| TestEntry { crc32, ..entry };
+        let entries = [
+            crc(1, TestEntry::preload("vmt", "materials", "a", b"")),
+            crc(2, TestEntry::preload("vmt", "Materials\\Brick", "b", b"")),
+            crc(3, TestEntry::preload("txt", "scripts", "items", b"")),
+            crc(4, TestEntry::preload(" ", " ", "readme", b"")),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let manifest: HashMap<String, u32> = [
+            ("materials/a.vmt", 1),
+            ("materials/brick/B.vmt", 2),
+            ("scripts/items.txt", 30),
+            ("scripts/missing.txt", 5),
+        ]
+        .into_iter()
+        .map(|(path, crc32)| (path.to_string(), crc32))
+        .collect();
+
+        let report = vpk.verify_manifest(&manifest);
+        assert_eq!(
+            report.matched,
+            ["Materials\\Brick/b.vmt", "materials/a.vmt"]
+        );
+        assert_eq!(
+            report.mismatched,
+            [ManifestMismatch {
+                path: "scripts/items.txt".to_string(),
+                expected: 30,
+                actual: 3,
+            }]
+        );
+        assert_eq!(report.missing, ["scripts/missing.txt"]);
+        assert_eq!(report.extra, ["readme"]);
+        assert!(!report.is_ok());
+    }
+}
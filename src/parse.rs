@@ -1,25 +1,57 @@
-use std::io::Read;
+use std::io::{Read, Write};
 
-pub(crate) fn read_u16(r: &mut impl Read) -> std::io::Result<u16> {
+use crate::structs::Endian;
+
+pub(crate) fn read_u16(r: &mut impl Read, endian: Endian) -> std::io::Result<u16> {
     let mut val = [0; 2];
 
     r.read_exact(&mut val)?;
 
-    Ok(u16::from_le_bytes(val))
+    Ok(match endian {
+        Endian::Little => u16::from_le_bytes(val),
+        Endian::Big => u16::from_be_bytes(val),
+    })
 }
 
-pub(crate) fn read_u32(r: &mut impl Read) -> std::io::Result<u32> {
+pub(crate) fn read_u32(r: &mut impl Read, endian: Endian) -> std::io::Result<u32> {
     let mut val = [0; 4];
 
     r.read_exact(&mut val)?;
 
-    Ok(u32::from_le_bytes(val))
+    Ok(match endian {
+        Endian::Little => u32::from_le_bytes(val),
+        Endian::Big => u32::from_be_bytes(val),
+    })
 }
 
-pub(crate) fn read_u128(r: &mut impl Read) -> std::io::Result<u128> {
+pub(crate) fn read_u128(r: &mut impl Read, endian: Endian) -> std::io::Result<u128> {
     let mut val = [0; 16];
 
     r.read_exact(&mut val)?;
 
-    Ok(u128::from_le_bytes(val))
+    Ok(match endian {
+        Endian::Little => u128::from_le_bytes(val),
+        Endian::Big => u128::from_be_bytes(val),
+    })
+}
+
+pub(crate) fn write_u16(w: &mut impl Write, val: u16, endian: Endian) -> std::io::Result<()> {
+    match endian {
+        Endian::Little => w.write_all(&val.to_le_bytes()),
+        Endian::Big => w.write_all(&val.to_be_bytes()),
+    }
+}
+
+pub(crate) fn write_u32(w: &mut impl Write, val: u32, endian: Endian) -> std::io::Result<()> {
+    match endian {
+        Endian::Little => w.write_all(&val.to_le_bytes()),
+        Endian::Big => w.write_all(&val.to_be_bytes()),
+    }
+}
+
+pub(crate) fn write_u128(w: &mut impl Write, val: u128, endian: Endian) -> std::io::Result<()> {
+    match endian {
+        Endian::Little => w.write_all(&val.to_le_bytes()),
+        Endian::Big => w.write_all(&val.to_be_bytes()),
+    }
 }
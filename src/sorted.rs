@@ -0,0 +1,168 @@
+use std::cmp::Ordering;
+
+use crate::access::{normalize_path_byte, DirFile};
+use crate::entry::VPKEntry;
+use crate::vpk::{Ext, VPKTree};
+use crate::VPK;
+
+/// Every entry of a VPK in a single `Vec`, sorted by extension, dir and filename, ignoring case.
+/// Lookups are a binary search, and iterating gives the entries in order. See
+/// [`VPK::sorted_tree`] and [`crate::vpk::ReadOptions::build_sorted_tree`].  
+/// This is a copy of the entries rather than a replacement for the maps of [`VPKTree`], so
+/// keeping one alongside the VPK roughly doubles the memory used by the entries. Lookups through
+/// [`VPK::get`] still use the maps, and are usually faster: each step of the binary search reads
+/// the path from a different place in the dir file data, so it is mostly cache misses.
+#[derive(Debug, Default, Clone)]
+pub struct SortedTree {
+    entries: Vec<(Ext<'static>, DirFile, VPKEntry)>,
+}
+impl SortedTree {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the entry, comparing the dir and filename case-insensitively, like
+    /// [`VPKTree::getf_ignore_case`].
+    pub fn get(&self, ext: &Ext<'_>, dir: &str, filename: &str) -> Option<&VPKEntry> {
+        self.get_full(ext, dir, filename).map(|(_, _, entry)| entry)
+    }
+
+    /// Like [`SortedTree::get`], but also gives the key the entry is stored under.
+    pub fn get_full(
+        &self,
+        ext: &Ext<'_>,
+        dir: &str,
+        filename: &str,
+    ) -> Option<(&Ext<'static>, &DirFile, &VPKEntry)> {
+        let index = self
+            .entries
+            .binary_search_by(|(entry_ext, dir_file, _)| {
+                cmp_key(
+                    (entry_ext, dir_file.dir(), dir_file.filename()),
+                    (ext, dir.as_bytes(), filename.as_bytes()),
+                )
+            })
+            .ok()?;
+
+        let (ext, dir_file, entry) = &self.entries[index];
+        Some((ext, dir_file, entry))
+    }
+
+    /// Iterate over the entries in sorted order
+    pub fn iter(&self) -> impl Iterator<Item = (&Ext<'static>, &DirFile, &VPKEntry)> {
+        self.entries
+            .iter()
+            .map(|(ext, dir_file, entry)| (ext, dir_file, entry))
+    }
+}
+impl<S> From<&VPKTree<S>> for SortedTree {
+    fn from(tree: &VPKTree<S>) -> SortedTree {
+        let mut entries: Vec<_> = tree
+            .iter_with_ext()
+            .map(|(ext, dir_file, entry)| (ext.into_owned(), dir_file.clone(), entry.clone()))
+            .collect();
+        entries.sort_unstable_by(|(a_ext, a, _), (b_ext, b, _)| {
+            cmp_key(
+                (a_ext, a.dir(), a.filename()),
+                (b_ext, b.dir(), b.filename()),
+            )
+        });
+
+        SortedTree { entries }
+    }
+}
+
+/// Order by extension, then dir, then filename. The extension is compared with [`Ext`]'s own
+/// ordering, the dir like [`DirFile`]'s equality, ignoring case and treating `\` as `/`, and the
+/// filename ignoring case.
+fn cmp_key(a: (&Ext<'_>, &[u8], &[u8]), b: (&Ext<'_>, &[u8], &[u8])) -> Ordering {
+    let (a_ext, a_dir, a_filename) = a;
+    let (b_ext, b_dir, b_filename) = b;
+
+    a_ext
+        .cmp(b_ext)
+        .then_with(|| {
+            let a_dir = a_dir.iter().map(|&v| normalize_path_byte(v));
+            a_dir.cmp(b_dir.iter().map(|&v| normalize_path_byte(v)))
+        })
+        .then_with(|| {
+            let a_filename = a_filename.iter().map(u8::to_ascii_lowercase);
+            a_filename.cmp(b_filename.iter().map(u8::to_ascii_lowercase))
+        })
+}
+
+impl<S> VPK<S> {
+    /// Copy every entry into a [`SortedTree`], for looking them up with a binary search.
+    /// Reading the data of an entry still needs the [`VPK`], see [`VPK::flat_handle`].
+    pub fn sorted_tree(&self) -> SortedTree {
+        SortedTree::from(&self.tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::vpk::tests::{build_vpk_v1, TestEntry};
+    use crate::vpk::{Ext, ProbableKind, ReadOptions};
+    use crate::VPK;
+
+    #[test]
+    fn test_sorted_tree() {
+        let entries = [
+            TestEntry::preload("vtf", "materials", "brick", b"abc"),
+            TestEntry::preload("vmt", "materials\\Concrete", "Floor", b"def"),
+            TestEntry::preload("vmt", "materials", "brick", b"ghi"),
+            TestEntry::preload("txt", "scripts", "items", b"jkl"),
+            TestEntry::preload("vmt", "materials", "Alpha", b"mno"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let options = ReadOptions {
+            build_sorted_tree: true,
+            ..ReadOptions::default()
+        };
+        let vpk = VPK::read_bytes_with_options(data.clone(), |i| i.to_string(), &options).unwrap();
+
+        let sorted = vpk.sorted().unwrap();
+        assert_eq!(sorted.len(), 5);
+        let keys: Vec<_> = sorted
+            .iter()
+            .map(|(ext, dir_file, _)| (ext.as_slice(), dir_file.filename()))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                (b"vmt".as_slice(), b"Alpha".as_slice()),
+                (b"vmt", b"brick"),
+                (b"vmt", b"Floor"),
+                (b"vtf", b"brick"),
+                (b"txt", b"items"),
+            ]
+        );
+
+        let (ext, dir_file, floor) = sorted
+            .get_full(&Ext::Vmt, "Materials/concrete", "floor")
+            .unwrap();
+        let handle = vpk.flat_handle(ext, dir_file, floor);
+        assert_eq!(handle.get().unwrap().as_ref(), b"def");
+
+        let txt = Ext::from_ext_slice(b"txt");
+        assert!(sorted.get(&txt, "SCRIPTS", "Items").is_some());
+        // Extensions are ordered like `Ext`, so other casings are found like with `VPK::get`
+        let upper_txt = Ext::Other(Cow::Borrowed(b"TXT".as_slice()));
+        assert!(sorted.get(&upper_txt, "scripts", "items").is_some());
+        assert!(vpk.get(&upper_txt, "scripts", "items").is_some());
+        let upper_vmt = Ext::Other(Cow::Borrowed(b"VMT".as_slice()));
+        assert!(sorted.get(&upper_vmt, "materials", "alpha").is_some());
+        assert!(sorted.get(&Ext::Vtf, "materials", "alpha").is_none());
+        assert!(sorted.get(&Ext::Mdl, "materials", "brick").is_none());
+
+        let read_without = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert!(read_without.sorted().is_none());
+        assert_eq!(read_without.sorted_tree().len(), 5);
+    }
+}
@@ -1,5 +1,16 @@
-use crate::parse::{read_u128, read_u32};
-use std::io::Read;
+use crate::parse::{read_u128, read_u32, write_u128, write_u32};
+use crate::Error;
+use std::io::{Read, Write};
+use std::ops::Range;
+
+/// The byte order of the numbers in a VPK.  
+/// PC VPKs are little endian, while Xbox 360 / PS3 VPKs are big endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct VPKHeader {
@@ -9,9 +20,17 @@ pub struct VPKHeader {
 }
 impl VPKHeader {
     pub fn read_le(r: &mut impl Read) -> std::io::Result<Self> {
-        let signature = read_u32(r)?;
-        let version = read_u32(r)?;
-        let tree_length = read_u32(r)?;
+        Self::read(r, Endian::Little)
+    }
+
+    pub fn read_be(r: &mut impl Read) -> std::io::Result<Self> {
+        Self::read(r, Endian::Big)
+    }
+
+    pub fn read(r: &mut impl Read, endian: Endian) -> std::io::Result<Self> {
+        let signature = read_u32(r, endian)?;
+        let version = read_u32(r, endian)?;
+        let tree_length = read_u32(r, endian)?;
 
         Ok(Self {
             signature,
@@ -19,6 +38,22 @@ impl VPKHeader {
             tree_length,
         })
     }
+
+    pub fn write_le(&self, w: &mut impl Write) -> std::io::Result<()> {
+        self.write(w, Endian::Little)
+    }
+
+    pub fn write_be(&self, w: &mut impl Write) -> std::io::Result<()> {
+        self.write(w, Endian::Big)
+    }
+
+    pub fn write(&self, w: &mut impl Write, endian: Endian) -> std::io::Result<()> {
+        write_u32(w, self.signature, endian)?;
+        write_u32(w, self.version, endian)?;
+        write_u32(w, self.tree_length, endian)?;
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -30,10 +65,18 @@ pub struct VPKHeaderV2 {
 }
 impl VPKHeaderV2 {
     pub fn read_le(r: &mut impl Read) -> std::io::Result<Self> {
-        let embed_chunk_length = read_u32(r)?;
-        let chunk_hashes_length = read_u32(r)?;
-        let self_hashes_length = read_u32(r)?;
-        let signature_length = read_u32(r)?;
+        Self::read(r, Endian::Little)
+    }
+
+    pub fn read_be(r: &mut impl Read) -> std::io::Result<Self> {
+        Self::read(r, Endian::Big)
+    }
+
+    pub fn read(r: &mut impl Read, endian: Endian) -> std::io::Result<Self> {
+        let embed_chunk_length = read_u32(r, endian)?;
+        let chunk_hashes_length = read_u32(r, endian)?;
+        let self_hashes_length = read_u32(r, endian)?;
+        let signature_length = read_u32(r, endian)?;
 
         Ok(Self {
             embed_chunk_length,
@@ -42,6 +85,169 @@ impl VPKHeaderV2 {
             signature_length,
         })
     }
+
+    pub fn write_le(&self, w: &mut impl Write) -> std::io::Result<()> {
+        self.write(w, Endian::Little)
+    }
+
+    pub fn write_be(&self, w: &mut impl Write) -> std::io::Result<()> {
+        self.write(w, Endian::Big)
+    }
+
+    pub fn write(&self, w: &mut impl Write, endian: Endian) -> std::io::Result<()> {
+        write_u32(w, self.embed_chunk_length, endian)?;
+        write_u32(w, self.chunk_hashes_length, endian)?;
+        write_u32(w, self.self_hashes_length, endian)?;
+        write_u32(w, self.signature_length, endian)?;
+
+        Ok(())
+    }
+}
+
+/// The header Valve puts before LZMA compressed data, in place of the standard `.lzma` header.
+/// It is always little endian.  
+/// See [`crate::vpk::ReadOptions::tree_compression`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LzmaHeader {
+    /// The size of the data once decompressed
+    pub actual_size: u32,
+    /// The size of the compressed data after the header
+    pub lzma_size: u32,
+    pub properties: [u8; 5],
+}
+impl LzmaHeader {
+    pub const ID: [u8; 4] = *b"LZMA";
+    pub const SIZE: usize = 17;
+
+    /// Read the header from the start of `data`, if it starts with [`LzmaHeader::ID`]
+    pub fn read(data: &[u8]) -> Option<LzmaHeader> {
+        let header = data.get(..LzmaHeader::SIZE)?;
+        if header[..4] != LzmaHeader::ID {
+            return None;
+        }
+
+        let mut r = &header[4..];
+        let actual_size = read_u32(&mut r, Endian::Little).ok()?;
+        let lzma_size = read_u32(&mut r, Endian::Little).ok()?;
+        let mut properties = [0; 5];
+        properties.copy_from_slice(r);
+
+        Some(LzmaHeader {
+            actual_size,
+            lzma_size,
+            properties,
+        })
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> std::io::Result<()> {
+        w.write_all(&LzmaHeader::ID)?;
+        write_u32(w, self.actual_size, Endian::Little)?;
+        write_u32(w, self.lzma_size, Endian::Little)?;
+        w.write_all(&self.properties)
+    }
+}
+
+/// Owned copies of the small regions at the end of a version 2 dir file, so that they can be
+/// kept around without the rest of the file. See [`crate::vpk::ReadOptions::keep_v2_regions`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VPKHeaderV2Regions {
+    /// MD5s of the chunks of the archive files
+    pub chunk_hashes: Vec<u8>,
+    /// The [`VPKHeaderV2Checksum`], as it is stored
+    pub self_hashes: Vec<u8>,
+    /// The signature region, see [`crate::VPK::signature_region`]. Empty if the VPK isn't signed.
+    pub signature: Vec<u8>,
+}
+impl VPKHeaderV2Regions {
+    /// Copy the regions out of the dir file `data`.  
+    /// Returns `None` if any of them are outside of it.
+    pub fn new(data: &[u8], layout: &VPKHeaderV2Layout) -> Option<Self> {
+        Some(Self {
+            chunk_hashes: data.get(layout.chunk_hashes.clone())?.to_vec(),
+            self_hashes: data.get(layout.self_hashes.clone())?.to_vec(),
+            signature: data.get(layout.signature.clone())?.to_vec(),
+        })
+    }
+}
+
+/// The byte ranges of each region in a version 2 dir file, in the order they appear.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VPKHeaderV2Layout {
+    pub tree: Range<usize>,
+    /// File data embedded directly in the dir file
+    pub embed_chunk: Range<usize>,
+    /// MD5s of the chunks of the archive files
+    pub chunk_hashes: Range<usize>,
+    /// The [`VPKHeaderV2Checksum`]
+    pub self_hashes: Range<usize>,
+    pub signature: Range<usize>,
+}
+impl VPKHeaderV2Layout {
+    /// Compute the regions from the lengths in the headers.  
+    /// This doesn't check that the regions are within the file, see
+    /// [`VPKHeaderV2Layout::try_new`]. Bogus lengths saturate rather than overflowing.
+    pub fn new(header_length: u32, header: &VPKHeader, header_v2: &VPKHeaderV2) -> Self {
+        let [tree, embed_chunk, chunk_hashes, self_hashes, signature] =
+            Self::regions(header_length, header, header_v2).map(|(_, range)| {
+                let to_usize = |v: u64| usize::try_from(v).unwrap_or(usize::MAX);
+                to_usize(range.start)..to_usize(range.end)
+            });
+
+        Self {
+            tree,
+            embed_chunk,
+            chunk_hashes,
+            self_hashes,
+            signature,
+        }
+    }
+
+    /// Like [`VPKHeaderV2Layout::new`], but fails with [`Error::LayoutOutOfBounds`] if any of the
+    /// regions go past `data_len`, the length of the dir file.
+    pub fn try_new(
+        header_length: u32,
+        header: &VPKHeader,
+        header_v2: &VPKHeaderV2,
+        data_len: usize,
+    ) -> Result<Self, Error> {
+        for (region, range) in Self::regions(header_length, header, header_v2) {
+            if range.end > data_len as u64 {
+                return Err(Error::LayoutOutOfBounds {
+                    region,
+                    start: range.start,
+                    end: range.end,
+                    len: data_len,
+                });
+            }
+        }
+
+        // Everything fits in `data_len`, so none of them saturated
+        Ok(Self::new(header_length, header, header_v2))
+    }
+
+    /// The name and range of each region. These are all `u32` lengths, so summing them in `u64`
+    /// can't overflow.
+    fn regions(
+        header_length: u32,
+        header: &VPKHeader,
+        header_v2: &VPKHeaderV2,
+    ) -> [(&'static str, Range<u64>); 5] {
+        let lengths = [
+            ("tree", header.tree_length),
+            ("embed chunk", header_v2.embed_chunk_length),
+            ("chunk hashes", header_v2.chunk_hashes_length),
+            ("self hashes", header_v2.self_hashes_length),
+            ("signature", header_v2.signature_length),
+        ];
+
+        let mut start = u64::from(header_length);
+        lengths.map(|(region, length)| {
+            let end = start + u64::from(length);
+            let range = start..end;
+            start = end;
+            (region, range)
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -51,10 +257,12 @@ pub struct VPKHeaderV2Checksum {
     pub file_checksum: u128,
 }
 impl VPKHeaderV2Checksum {
+    /// The checksums are MD5 digests, which are bytes rather than numbers, so they are always
+    /// read as little endian regardless of the VPK's [`Endian`].
     pub fn read_le(r: &mut impl Read) -> std::io::Result<Self> {
-        let tree_checksum = read_u128(r)?;
-        let chunk_hashes_checksum = read_u128(r)?;
-        let file_checksum = read_u128(r)?;
+        let tree_checksum = read_u128(r, Endian::Little)?;
+        let chunk_hashes_checksum = read_u128(r, Endian::Little)?;
+        let file_checksum = read_u128(r, Endian::Little)?;
 
         Ok(Self {
             tree_checksum,
@@ -62,4 +270,116 @@ impl VPKHeaderV2Checksum {
             file_checksum,
         })
     }
+
+    pub fn write_le(&self, w: &mut impl Write) -> std::io::Result<()> {
+        write_u128(w, self.tree_checksum, Endian::Little)?;
+        write_u128(w, self.chunk_hashes_checksum, Endian::Little)?;
+        write_u128(w, self.file_checksum, Endian::Little)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VPKHeader, VPKHeaderV2, VPKHeaderV2Checksum, VPKHeaderV2Layout};
+    use crate::entry::VPKDirectoryEntry;
+    use crate::vpk::tests::{build_vpk_v2, TestEntry};
+    use crate::vpk::{ProbableKind, VPK_SIGNATURE};
+    use crate::{Error, VPK};
+
+    #[test]
+    fn test_write_le_round_trip() {
+        let header = VPKHeader {
+            signature: VPK_SIGNATURE,
+            version: 2,
+            tree_length: 1234,
+        };
+        let mut out = Vec::new();
+        header.write_le(&mut out).unwrap();
+        assert_eq!(out.len(), 12);
+        assert_eq!(VPKHeader::read_le(&mut out.as_slice()).unwrap(), header);
+
+        let header_v2 = VPKHeaderV2 {
+            embed_chunk_length: 1,
+            chunk_hashes_length: 2,
+            self_hashes_length: 48,
+            signature_length: 3,
+        };
+        let mut out = Vec::new();
+        header_v2.write_le(&mut out).unwrap();
+        assert_eq!(out.len(), 16);
+        assert_eq!(
+            VPKHeaderV2::read_le(&mut out.as_slice()).unwrap(),
+            header_v2
+        );
+
+        let checksum = VPKHeaderV2Checksum {
+            tree_checksum: 1,
+            chunk_hashes_checksum: 2 << 100,
+            file_checksum: u128::MAX,
+        };
+        let mut out = Vec::new();
+        checksum.write_le(&mut out).unwrap();
+        assert_eq!(out.len(), 48);
+        assert_eq!(
+            VPKHeaderV2Checksum::read_le(&mut out.as_slice()).unwrap(),
+            checksum
+        );
+
+        let dir_entry = VPKDirectoryEntry {
+            crc32: 0xdeadbeef,
+            preload_length: 3,
+            archive_index: 7,
+            archive_offset: 0x1000,
+            file_length: 0x2000,
+            suffix: 0xffff,
+        };
+        let mut out = Vec::new();
+        dir_entry.write_le(&mut out).unwrap();
+        assert_eq!(out.len(), 18);
+        assert_eq!(
+            VPKDirectoryEntry::read_le(&mut out.as_slice()).unwrap(),
+            dir_entry
+        );
+
+        let mut out = Vec::new();
+        dir_entry.write_be(&mut out).unwrap();
+        assert_eq!(&out[..4], &[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(
+            VPKDirectoryEntry::read_be(&mut out.as_slice()).unwrap(),
+            dir_entry
+        );
+    }
+
+    #[test]
+    fn test_v2_layout_out_of_bounds() {
+        let entries = [TestEntry::preload("vmt", "materials", "a", b"abc")];
+        let mut data = build_vpk_v2(&entries, b"");
+        // signature_length
+        data[24..28].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let err = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::LayoutOutOfBounds { region: "signature", end, len, .. }
+                if end > u64::from(u32::MAX) && len == data.len()
+        ));
+
+        // Huge lengths don't overflow when the layout isn't checked
+        let header = VPKHeader {
+            signature: VPK_SIGNATURE,
+            version: 2,
+            tree_length: u32::MAX,
+        };
+        let header_v2 = VPKHeaderV2 {
+            embed_chunk_length: u32::MAX,
+            chunk_hashes_length: u32::MAX,
+            self_hashes_length: 48,
+            signature_length: u32::MAX,
+        };
+        let layout = VPKHeaderV2Layout::new(28, &header, &header_v2);
+        assert_eq!(layout.tree, 28..28 + u32::MAX as usize);
+        assert!(VPKHeaderV2Layout::try_new(28, &header, &header_v2, 1024).is_err());
+    }
 }
@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::hash::BuildHasher;
+use std::io::{Read, Seek};
+use std::path::{Component, Path, PathBuf};
+
+use crate::access::path_eq_ignore_case;
+use crate::entry::{VPKEntryHandle, VpkEntryReader};
+use crate::vpk::Ext;
+use crate::VPK;
+
+/// A read-only filesystem, so that VPKs can be used in the same way as loose files on disk.  
+/// Paths are relative, like `materials/brick/wall01.vtf`, and use `/` as the separator.
+pub trait VirtualFs {
+    type Reader<'a>: Read + Seek + 'a
+    where
+        Self: 'a;
+
+    /// Open the file at `path` for reading.  
+    /// Fails with [`std::io::ErrorKind::NotFound`] if there's no file at `path`.
+    fn open(&self, path: &str) -> std::io::Result<Self::Reader<'_>>;
+
+    /// Whether there is a file at `path`
+    fn exists(&self, path: &str) -> bool;
+
+    /// List the files and directories directly in the directory at `path`, sorted by name.  
+    /// An empty `path` is the root.
+    fn read_dir(&self, path: &str) -> std::io::Result<Vec<VfsDirEntry>>;
+}
+
+/// An entry in a directory, see [`VirtualFs::read_dir`]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VfsDirEntry {
+    /// The name of the file, with its extension, or of the directory
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// Paths are compared case-insensitively, like [`VPK::get_ignore_case`].  
+/// `read_dir` goes over every entry of the VPK on each call, so listing many directories is
+/// slow for large VPKs. Use [`VPK::iter`] to go over the entries once instead.
+impl<S: BuildHasher> VirtualFs for VPK<S> {
+    type Reader<'a>
+        = VpkEntryReader<'a>
+    where
+        S: 'a;
+
+    fn open(&self, path: &str) -> std::io::Result<VpkEntryReader<'_>> {
+        let handle = self.get_path(path).ok_or_else(|| not_found(path))?;
+        handle.to_reader()
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.get_path(path).is_some()
+    }
+
+    fn read_dir(&self, path: &str) -> std::io::Result<Vec<VfsDirEntry>> {
+        let path = path.trim_matches(['/', '\\']);
+
+        // Lowercased name -> entry, so that differently cased dirs are only listed once
+        let mut entries = BTreeMap::new();
+        for handle in self.iter() {
+            let dir = handle.dir_file.dir_or_empty();
+
+            let entry = if path_eq_ignore_case(dir, path.as_bytes()) {
+                VfsDirEntry {
+                    name: handle.filename_with_ext(),
+                    is_dir: false,
+                }
+            } else {
+                let Some(rest) = strip_dir_prefix(dir, path.as_bytes()) else {
+                    continue;
+                };
+                let name = rest.split(|&b| b == b'/' || b == b'\\').next().unwrap();
+                VfsDirEntry {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    is_dir: true,
+                }
+            };
+            entries
+                .entry((entry.name.to_ascii_lowercase(), entry.is_dir))
+                .or_insert(entry);
+        }
+
+        if entries.is_empty() && !path.is_empty() {
+            return Err(not_found(path));
+        }
+
+        Ok(entries.into_values().collect())
+    }
+}
+
+impl<S: BuildHasher> VPK<S> {
+    /// Get an entry by its full path, like `materials/brick/wall01.vtf`, ignoring case.
+    pub fn get_path<'s>(&'s self, path: &str) -> Option<VPKEntryHandle<'s, S>> {
+        let (ext, path) = Ext::from_path(path);
+        let (dir, filename) = path.rsplit_once(['/', '\\']).unwrap_or((" ", path));
+        self.get_ignore_case(&ext, dir, filename)
+    }
+}
+
+/// Loose files in a directory on disk, to use alongside VPKs through [`VirtualFs`].  
+/// Paths that would go outside of the directory, with `..` or by being absolute, fail with
+/// [`std::io::ErrorKind::InvalidInput`].
+#[derive(Debug, Clone)]
+pub struct LooseDir {
+    root: PathBuf,
+}
+impl LooseDir {
+    pub fn new(root: impl Into<PathBuf>) -> LooseDir {
+        LooseDir { root: root.into() }
+    }
+
+    pub fn root(&self) -> &std::path::Path {
+        &self.root
+    }
+
+    /// Get the path on disk of `path`, which can't go outside of the root, like with `..` or an
+    /// absolute path.
+    fn join(&self, path: &str) -> std::io::Result<PathBuf> {
+        let mut full = self.root.clone();
+        for component in Path::new(path).components() {
+            match component {
+                Component::Normal(name) => full.push(name),
+                Component::CurDir => {}
+                Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("{path} is outside of {}", self.root.display()),
+                    ));
+                }
+            }
+        }
+
+        Ok(full)
+    }
+}
+impl VirtualFs for LooseDir {
+    type Reader<'a> = File;
+
+    fn open(&self, path: &str) -> std::io::Result<File> {
+        File::open(self.join(path)?)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.join(path).is_ok_and(|path| path.is_file())
+    }
+
+    fn read_dir(&self, path: &str) -> std::io::Result<Vec<VfsDirEntry>> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(self.join(path)?)? {
+            let entry = entry?;
+            entries.push(VfsDirEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                is_dir: entry.file_type()?.is_dir(),
+            });
+        }
+        entries.sort();
+
+        Ok(entries)
+    }
+}
+
+/// If `dir` is a subdirectory of `parent`, get the rest of it after the separator
+fn strip_dir_prefix<'d>(dir: &'d [u8], parent: &[u8]) -> Option<&'d [u8]> {
+    if parent.is_empty() {
+        return (!dir.is_empty()).then_some(dir);
+    }
+
+    let prefix = dir.get(..parent.len())?;
+    if !path_eq_ignore_case(prefix, parent) {
+        return None;
+    }
+
+    match dir[parent.len()..] {
+        [b'/' | b'\\', ref rest @ ..] if !rest.is_empty() => Some(rest),
+        _ => None,
+    }
+}
+
+fn not_found(path: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("{path} is not in the VPK"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::{LooseDir, VfsDirEntry, VirtualFs};
+    use crate::vpk::tests::{build_vpk_v1, temp_dir, TestEntry};
+    use crate::vpk::ProbableKind;
+    use crate::VPK;
+
+    fn read_all(fs: &impl VirtualFs, path: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        fs.open(path).unwrap().read_to_end(&mut data).unwrap();
+        data
+    }
+
+    fn dir_entry(name: &str, is_dir: bool) -> VfsDirEntry {
+        VfsDirEntry {
+            name: name.to_string(),
+            is_dir,
+        }
+    }
+
+    #[test]
+    fn test_vpk_vfs() {
+        let entries = [
+            TestEntry::preload("vmt", "materials/brick", "a", b"abc"),
+            TestEntry::preload("vtf", "Materials\\Brick", "a", b"def"),
+            TestEntry::preload("vmt", "materials/metal/rust", "b", b""),
+            TestEntry::preload("vmt", "materials", "c", b""),
+            TestEntry::preload(" ", " ", "readme", b"hi"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        assert_eq!(read_all(&vpk, "materials/brick/a.vmt"), b"abc");
+        assert_eq!(read_all(&vpk, "materials/brick/A.VTF"), b"def");
+        assert_eq!(read_all(&vpk, "readme"), b"hi");
+        assert!(vpk.exists("materials/c.vmt"));
+        assert!(!vpk.exists("materials/brick"));
+        let err = vpk.open("materials/missing.vmt").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+        assert_eq!(
+            vpk.read_dir("").unwrap(),
+            [dir_entry("materials", true), dir_entry("readme", false)]
+        );
+        assert_eq!(
+            vpk.read_dir("materials/").unwrap(),
+            [
+                dir_entry("brick", true),
+                dir_entry("c.vmt", false),
+                dir_entry("metal", true),
+            ]
+        );
+        assert_eq!(
+            vpk.read_dir("materials/brick").unwrap(),
+            [dir_entry("a.vmt", false), dir_entry("a.vtf", false)]
+        );
+        assert_eq!(
+            vpk.read_dir("materials/metal").unwrap(),
+            [dir_entry("rust", true)]
+        );
+        assert!(vpk.read_dir("models").is_err());
+    }
+
+    #[test]
+    fn test_loose_dir_vfs() {
+        let root = temp_dir("loose_dir_vfs");
+        std::fs::create_dir_all(root.join("materials/brick")).unwrap();
+        std::fs::write(root.join("materials/brick/a.vmt"), b"abc").unwrap();
+        std::fs::write(root.join("materials/c.vmt"), b"").unwrap();
+        let fs = LooseDir::new(&*root);
+
+        assert_eq!(read_all(&fs, "materials/brick/a.vmt"), b"abc");
+        assert!(fs.exists("materials/c.vmt"));
+        assert!(!fs.exists("materials/brick"));
+
+        // Paths can't go outside of the root
+        std::fs::write(root.join("outside.vmt"), b"def").unwrap();
+        let fs = LooseDir::new(root.join("materials"));
+        assert!(!fs.exists("brick/../c.vmt"));
+        assert!(!fs.exists("../outside.vmt"));
+        assert!(fs.open("../outside.vmt").is_err());
+        assert!(fs.open(root.join("outside.vmt").to_str().unwrap()).is_err());
+        assert!(fs.read_dir("..").is_err());
+        assert!(fs.exists("./c.vmt"));
+        let fs = LooseDir::new(&*root);
+        assert_eq!(
+            fs.read_dir("materials").unwrap(),
+            [dir_entry("brick", true), dir_entry("c.vmt", false)]
+        );
+    }
+}
@@ -1,28 +1,33 @@
 use crate::access;
 use crate::access::DirFile;
 use crate::access::DirFileBigRef;
+use crate::access::DirFileBigRefExact;
 use crate::access::DirFileBigRefLowercase;
 use crate::access::DirFileEntryMap;
 use crate::access::DirFileRef;
 use crate::access::DirFileRefLowercase;
+use crate::access::MapRandomState;
 use crate::entry::*;
+use crate::sorted::SortedTree;
 use crate::structs::*;
 use crate::Error;
 
 use indexmap::Equivalent;
 use indexmap::IndexMap;
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 
 use std::fs::File;
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash};
 use std::io::Cursor;
 use std::io::{Seek, SeekFrom};
 use std::mem;
 use std::ops::Range;
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
 
-const VPK_SIGNATURE: u32 = 0x55aa1234;
+pub(crate) const VPK_SIGNATURE: u32 = 0x55aa1234;
 const VPK_SELF_HASHES_LENGTH: u32 = 48;
 
 // TODO: This is still not as fast as I'd like it to be.
@@ -69,8 +74,13 @@ const VPK_SELF_HASHES_LENGTH: u32 = 48;
 
 // TODO: comments about what these are
 // TODO: add more, possibly remove uncommon or less useful entries
-/// Extensions
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Extensions  
+/// These are ordered with the known extensions first, in the order they are declared, followed
+/// by the [`Ext::Other`] extensions ordered by their bytes.  
+/// An [`Ext::Other`] holding a known extension, like `Ext::Other(b"vmt")`, is equal to the
+/// dedicated variant. Extensions are compared ignoring ASCII case, the same as
+/// [`VPKTree::for_ext`] finds their map, so `Ext::Other(b"VMT")` is equal to it as well.
+#[derive(Debug, Clone)]
 pub enum Ext<'a> {
     Vmt,
     Vtf,
@@ -93,6 +103,42 @@ pub enum Ext<'a> {
     Other(Cow<'a, [u8]>),
 }
 impl<'a> Ext<'a> {
+    const KNOWN: [Ext<'static>; 18] = [
+        Ext::Vmt,
+        Ext::Vtf,
+        Ext::Vtx,
+        Ext::Vvd,
+        Ext::Phy,
+        Ext::Res,
+        Ext::Mdl,
+        Ext::Scr,
+        Ext::Xsc,
+        Ext::Gam,
+        Ext::Lst,
+        Ext::Dsp,
+        Ext::Ico,
+        Ext::Icns,
+        Ext::Bmp,
+        Ext::Dat,
+        Ext::Wav,
+        Ext::Mp3,
+    ];
+
+    /// The position of the extension in [`Ext::KNOWN`], also checking the contents of
+    /// [`Ext::Other`]
+    fn known_index(&self) -> Option<usize> {
+        let s = self.as_slice();
+        Self::KNOWN
+            .iter()
+            .position(|ext| ext.as_slice().eq_ignore_ascii_case(s))
+    }
+
+    /// Known extensions in declaration order, then the others by their lowercased bytes
+    fn order_key(&self) -> (usize, impl Iterator<Item = u8> + '_) {
+        let index = self.known_index().unwrap_or(Self::KNOWN.len());
+        (index, self.as_slice().iter().map(u8::to_ascii_lowercase))
+    }
+
     pub fn as_slice(&self) -> &[u8] {
         match self {
             Ext::Vmt => b"vmt",
@@ -141,6 +187,48 @@ impl<'a> Ext<'a> {
         }
     }
 
+    pub fn into_owned(self) -> Ext<'static> {
+        match self {
+            Ext::Vmt => Ext::Vmt,
+            Ext::Vtf => Ext::Vtf,
+            Ext::Vtx => Ext::Vtx,
+            Ext::Vvd => Ext::Vvd,
+            Ext::Phy => Ext::Phy,
+            Ext::Res => Ext::Res,
+            Ext::Mdl => Ext::Mdl,
+            Ext::Scr => Ext::Scr,
+            Ext::Xsc => Ext::Xsc,
+            Ext::Gam => Ext::Gam,
+            Ext::Lst => Ext::Lst,
+            Ext::Dsp => Ext::Dsp,
+            Ext::Ico => Ext::Ico,
+            Ext::Icns => Ext::Icns,
+            Ext::Bmp => Ext::Bmp,
+            Ext::Dat => Ext::Dat,
+            Ext::Wav => Ext::Wav,
+            Ext::Mp3 => Ext::Mp3,
+            Ext::Other(s) => Ext::Other(Cow::Owned(s.into_owned())),
+        }
+    }
+
+    /// Split a full path like `materials/brick/a.dx90.vtx` into its extension and the path
+    /// without it, `(Ext::Vtx, "materials/brick/a.dx90")`.  
+    /// Only the last extension is used, since that's what VPKs store. A path with no extension
+    /// gives `Ext::Other(b" ")`, as VPKs store a missing extension as a single space.
+    pub fn from_path(path: &'a str) -> (Ext<'a>, &'a str) {
+        let name_start = path.rfind(['/', '\\']).map_or(0, |i| i + 1);
+        match path[name_start..].rfind('.') {
+            Some(dot) => {
+                let dot = name_start + dot;
+                (
+                    Ext::from_ext_slice(&path.as_bytes()[dot + 1..]),
+                    &path[..dot],
+                )
+            }
+            None => (Ext::Other(Cow::Borrowed(b" ")), path),
+        }
+    }
+
     pub fn from_ext_slice(s: &'a [u8]) -> Ext<'a> {
         let s = if s.iter().all(|c| c.is_ascii_lowercase()) {
             Cow::Borrowed(s)
@@ -171,6 +259,114 @@ impl<'a> Ext<'a> {
     }
 }
 
+impl<'a> From<&'a [u8]> for Ext<'a> {
+    fn from(s: &'a [u8]) -> Ext<'a> {
+        Ext::from_ext_slice(s)
+    }
+}
+impl<'a> From<&'a str> for Ext<'a> {
+    fn from(s: &'a str) -> Ext<'a> {
+        Ext::from_ext_slice(s.as_bytes())
+    }
+}
+impl From<Vec<u8>> for Ext<'static> {
+    fn from(mut s: Vec<u8>) -> Ext<'static> {
+        s.make_ascii_lowercase();
+        match Ext::from_ext_slice(&s) {
+            Ext::Other(_) => {}
+            known => return known.into_owned(),
+        }
+
+        // Reuse the allocation rather than copying like `into_owned` would
+        Ext::Other(Cow::Owned(s))
+    }
+}
+impl From<String> for Ext<'static> {
+    fn from(s: String) -> Ext<'static> {
+        Ext::from(s.into_bytes())
+    }
+}
+impl PartialEq for Ext<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        // Every known extension has distinct bytes, so this also makes `Other` equal to them
+        self.as_slice().eq_ignore_ascii_case(other.as_slice())
+    }
+}
+impl Eq for Ext<'_> {}
+impl Hash for Ext<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        let s = self.as_slice();
+        state.write_usize(s.len());
+        for c in s {
+            state.write_u8(c.to_ascii_lowercase());
+        }
+    }
+}
+impl PartialOrd for Ext<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Ext<'_> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let (index, bytes) = self.order_key();
+        let (other_index, other_bytes) = other.order_key();
+        index.cmp(&other_index).then_with(|| bytes.cmp(other_bytes))
+    }
+}
+
+/// The key of an extension in [`VPKTree::other`].  
+/// Extensions are bytes in the VPK and aren't necessarily valid UTF-8, so formatting this shows
+/// printable ASCII as is and escapes the rest, like `b"\xff"` would be shown as `\xff`.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct ExtBytes(pub Vec<u8>);
+impl ExtBytes {
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+}
+impl std::ops::Deref for ExtBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+impl std::borrow::Borrow<[u8]> for ExtBytes {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+impl AsRef<[u8]> for ExtBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+impl From<Vec<u8>> for ExtBytes {
+    fn from(v: Vec<u8>) -> ExtBytes {
+        ExtBytes(v)
+    }
+}
+impl From<&[u8]> for ExtBytes {
+    fn from(v: &[u8]) -> ExtBytes {
+        ExtBytes(v.to_vec())
+    }
+}
+impl std::fmt::Display for ExtBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.escape_ascii())
+    }
+}
+impl std::fmt::Debug for ExtBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\"", self.0.escape_ascii())
+    }
+}
+
 // TODO: optionally check checksum
 // TODO: Should we also lowercase non-ascii text? Windows
 // does that.
@@ -194,29 +390,605 @@ pub enum ProbableKind {
     Hl2MiscSound,
 }
 
+/// How the directory tree is stored, see [`ReadOptions::tree_compression`].  
+/// This isn't part of the VPK format, but some tools compress the tree of their VPKs with LZMA,
+/// using the same [`LzmaHeader`] that Valve uses for other compressed data.
+/// The default is [`TreeCompression::Detect`] with the `lzma` feature, and
+/// [`TreeCompression::None`] without it, so that plain VPKs never fail to read because of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TreeCompression {
+    /// Decompress the tree if it starts with an [`LzmaHeader`] whose data fits in the tree
+    #[cfg_attr(feature = "lzma", default)]
+    Detect,
+    /// Always read the tree as is
+    #[cfg_attr(not(feature = "lzma"), default)]
+    None,
+    /// The tree is always LZMA compressed, so it is an error if it has no [`LzmaHeader`]
+    Lzma,
+}
+
+/// Options for [`VPK::read_with_options`].  
+/// `S` builds the hashers for the maps of the tree, see [`ReadOptions::hash_builder`].
+#[derive(Debug, Clone)]
+pub struct ReadOptions<S = MapRandomState> {
+    pub probable_kind: ProbableKind,
+    /// The directory that the numbered archive files (`pak01_000.vpk`, ...) live in.  
+    /// If `None`, they are assumed to be next to the dir file.  
+    /// Reading fails with [`Error::NonUtf8ArchiveDir`] if it isn't valid UTF-8.
+    pub archive_dir: Option<PathBuf>,
+    /// Check that every archive file referenced by an entry exists, returning
+    /// [`Error::MissingArchives`] if any do not.
+    pub validate_archives: bool,
+    /// The byte order of the VPK. If `None`, it is detected from the signature.
+    pub endian: Option<Endian>,
+    /// Make entries with the same dir share the same range of the data, even across extensions.
+    /// Each dir is already only stored once per extension, so this makes
+    /// [`DirFile::dir_range`] usable as an id for the dir across the whole VPK.  
+    /// Lookups don't get any faster from this, since they compare the path they're given
+    /// against the bytes of the dir either way.
+    pub intern_dirs: bool,
+    /// Skip malformed entries rather than failing the whole read. They are recorded in
+    /// [`VPK::skipped_entries`].  
+    /// If the tree is too broken to continue, then the entries read so far are kept.  
+    /// This also accepts version 2 VPKs whose self hashes aren't the usual 48 bytes, as long as
+    /// every region is still within the file.
+    pub lenient: bool,
+    /// Store the dirs and filenames lowercased, with `\` as `/`, so that [`VPK::get`] can
+    /// compare them exactly rather than ignoring case, which is faster.  
+    /// This keeps a normalized copy of the dir file for the keys, so it uses more memory, and
+    /// [`DirFile`]s no longer have the original casing. The arguments to [`VPK::get`] must then
+    /// be lowercase, as its docs already ask for.
+    pub normalize_case: bool,
+    /// Where the VPK starts in the data, for VPKs embedded in a larger file after some other
+    /// header. Everything before it is ignored, and every offset in the VPK is relative to it.
+    pub start_offset: usize,
+    /// What to do when two entries have the same extension, dir and filename, since only one of
+    /// them can be kept.  
+    /// [`OnDuplicate::Overwrite`] and [`OnDuplicate::Report`] keep the later entry.
+    /// [`OnDuplicate::Error`] fails the read, or with [`ReadOptions::lenient`] keeps the earlier
+    /// entry and skips the later one.
+    pub on_duplicate: OnDuplicate,
+    /// Build the full path of every entry while reading, so that [`VPK::iter_paths`] can borrow
+    /// them rather than allocating a `String` for each entry every time.  
+    /// This keeps every path in memory, which is about as large as the tree part of the dir file
+    /// again, plus a `usize` per entry.
+    pub store_full_paths: bool,
+    /// Share the archive paths with every other VPK read with the same cache, rather than each
+    /// VPK allocating its own. This is useful when reading the same VPKs many times, like when
+    /// remounting them.
+    pub archive_path_cache: Option<ArchivePathCache>,
+    /// Builds the hashers for the maps of the tree. The default is `ahash`, use
+    /// [`ReadOptions::with_hasher`] to change it, like to a [`SipRandomState`](access::SipRandomState) for VPKs from
+    /// untrusted sources.  
+    /// `ahash` is faster, but a VPK with paths crafted to collide could make lookups in it, and
+    /// reading it, very slow. SipHash resists that, in exchange for somewhat slower lookups.
+    pub hash_builder: S,
+    /// Trim trailing whitespace and control bytes, like a stray `\r`, from each dir and filename.
+    /// This is a compatibility shim for VPKs written by buggy tools, whose entries could
+    /// otherwise never be looked up. The dir of entries in the root, a single space, is kept.
+    pub trim_paths: bool,
+    /// Only keep the entries with these extensions. The whole tree still has to be walked, but
+    /// the other entries are skipped over without being stored, which saves memory and time when
+    /// only a few kinds of files are needed, like just the models.
+    pub ext_filter: Option<HashSet<Ext<'static>>>,
+    /// Fail with [`Error::TooManyEntries`] once the tree has more entries than this, rather than
+    /// growing the maps without bound. This guards against VPKs from untrusted sources that
+    /// claim a huge tree, along with a hardened [`ReadOptions::hash_builder`].  
+    /// This also bounds [`VPK::skipped_entries`] when reading with [`ReadOptions::lenient`].
+    pub max_entries: Option<usize>,
+    /// Keep copies of the chunk hashes, self hashes, and signature regions of a version 2 VPK in
+    /// [`VPK::v2_regions`]. They are small, and stay available for re-signing even without the
+    /// rest of the dir file.
+    pub keep_v2_regions: bool,
+    /// Also build a [`SortedTree`] after reading, available from [`VPK::sorted`].  
+    /// This is in addition to the maps of the tree rather than instead of them, and it copies
+    /// every entry, which roughly doubles the memory used by the entries. It is mostly useful for
+    /// going over the entries in order, see [`SortedTree`] for how lookups compare.
+    pub build_sorted_tree: bool,
+    /// Whether the directory tree may be compressed. With the `lzma` feature it is decompressed
+    /// by default if it starts with Valve's LZMA header, see [`TreeCompression`].  
+    /// A decompressed tree replaces the compressed one in [`VPK::data`], so the MD5 of the tree
+    /// no longer matches the one in the dir file and [`VPK::verify_checksums`] fails.
+    pub tree_compression: TreeCompression,
+    /// Fail with [`Error::TreeTooLarge`] if a compressed tree claims to decompress to more bytes
+    /// than this, rather than allocating whatever the header asks for. The default is
+    /// [`ReadOptions::DEFAULT_MAX_TREE_SIZE`].
+    pub max_tree_size: u32,
+    /// Called for every entry as it is read. This allows building your own index of the entries
+    /// in the same pass as the parse, rather than iterating over the whole tree afterwards.
+    pub visitor: Option<Arc<dyn ReadVisitor>>,
+    /// Gather [`ParseStats`] while reading, available from [`VPK::parse_stats`].
+    pub record_stats: bool,
+}
+impl ReadOptions {
+    /// 256 MiB, far larger than the trees of even the biggest games' VPKs
+    pub const DEFAULT_MAX_TREE_SIZE: u32 = 256 * 1024 * 1024;
+
+    pub fn new(probable_kind: ProbableKind) -> ReadOptions {
+        ReadOptions {
+            probable_kind,
+            archive_dir: None,
+            validate_archives: false,
+            endian: None,
+            intern_dirs: false,
+            lenient: false,
+            normalize_case: false,
+            start_offset: 0,
+            on_duplicate: OnDuplicate::Overwrite,
+            store_full_paths: false,
+            archive_path_cache: None,
+            hash_builder: MapRandomState::default(),
+            trim_paths: false,
+            ext_filter: None,
+            max_entries: None,
+            keep_v2_regions: false,
+            build_sorted_tree: false,
+            tree_compression: TreeCompression::default(),
+            max_tree_size: ReadOptions::DEFAULT_MAX_TREE_SIZE,
+            visitor: None,
+            record_stats: false,
+        }
+    }
+}
+impl<S> ReadOptions<S> {
+    /// Use `hash_builder` for the maps of the tree rather than the current one.
+    /// ```rust
+    /// use vpk::access::SipRandomState;
+    /// use vpk::vpk::ReadOptions;
+    ///
+    /// let options = ReadOptions::default().with_hasher(SipRandomState::new());
+    /// ```
+    pub fn with_hasher<T>(self, hash_builder: T) -> ReadOptions<T> {
+        ReadOptions {
+            probable_kind: self.probable_kind,
+            archive_dir: self.archive_dir,
+            validate_archives: self.validate_archives,
+            endian: self.endian,
+            intern_dirs: self.intern_dirs,
+            lenient: self.lenient,
+            normalize_case: self.normalize_case,
+            start_offset: self.start_offset,
+            on_duplicate: self.on_duplicate,
+            store_full_paths: self.store_full_paths,
+            archive_path_cache: self.archive_path_cache,
+            hash_builder,
+            trim_paths: self.trim_paths,
+            ext_filter: self.ext_filter,
+            max_entries: self.max_entries,
+            keep_v2_regions: self.keep_v2_regions,
+            build_sorted_tree: self.build_sorted_tree,
+            tree_compression: self.tree_compression,
+            max_tree_size: self.max_tree_size,
+            visitor: self.visitor,
+            record_stats: self.record_stats,
+        }
+    }
+}
+impl<S: Clone> ReadOptions<S> {
+    fn empty_tree(&self) -> VPKTree<S> {
+        VPKTree::new_with_hasher(self.probable_kind, self.hash_builder.clone())
+    }
+}
+impl Default for ReadOptions {
+    fn default() -> ReadOptions {
+        ReadOptions::new(ProbableKind::None)
+    }
+}
+
+/// Interns archive paths across VPKs, see [`ReadOptions::archive_path_cache`].  
+/// Clones share the same cache, and it can be used from multiple threads.  
+/// Only whole paths are shared, so VPKs in the same directory with different archive names still
+/// each have their own copy of the directory part.  
+/// Paths that no VPK uses anymore are dropped from the cache as it grows, or all at once with
+/// [`ArchivePathCache::remove_unused`].
+#[derive(Debug, Default, Clone)]
+pub struct ArchivePathCache {
+    inner: Arc<Mutex<ArchivePathCacheInner>>,
+}
+#[derive(Debug, Default)]
+struct ArchivePathCacheInner {
+    paths: HashSet<Arc<str>>,
+    /// Remove the unused paths once there are this many, so that the cache doesn't grow forever
+    /// as VPKs are dropped and others are read
+    prune_at: usize,
+}
+impl ArchivePathCacheInner {
+    fn remove_unused(&mut self) {
+        // The cache's own reference is the only one left
+        self.paths.retain(|path| Arc::strong_count(path) > 1);
+        self.prune_at = (self.paths.len() * 2).max(64);
+    }
+}
+impl ArchivePathCache {
+    pub fn new() -> ArchivePathCache {
+        ArchivePathCache::default()
+    }
+
+    /// Get the shared copy of `path`, adding it if it isn't in the cache yet
+    pub fn intern(&self, path: String) -> Arc<str> {
+        let mut inner = self.lock();
+        if let Some(path) = inner.paths.get(path.as_str()) {
+            return path.clone();
+        }
+
+        if inner.paths.len() >= inner.prune_at {
+            inner.remove_unused();
+        }
+        let path: Arc<str> = Arc::from(path);
+        inner.paths.insert(path.clone());
+        path
+    }
+
+    /// Drop every path that isn't used by a VPK anymore
+    pub fn remove_unused(&self) {
+        self.lock().remove_unused();
+    }
+
+    /// The number of distinct paths in the cache
+    pub fn len(&self) -> usize {
+        self.lock().paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ArchivePathCacheInner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Called for every entry as it is read, see [`ReadOptions::visitor`].  
+/// This is implemented for closures with the same arguments as [`ReadVisitor::visit`].  
+/// The options can be shared between threads and reads, so the visitor only gets `&self`, and
+/// should use a `Mutex` or similar for whatever it collects.
+pub trait ReadVisitor: Send + Sync {
+    /// `dir` and `filename` are the bytes as they are stored in the dir file, so a dir may use
+    /// `\` as the separator, and is a single space if the entry has no dir. With
+    /// [`ReadOptions::trim_paths`] they are already trimmed. The `ext` is lowercased.  
+    /// `entry` is the entry as it is stored in the tree, so for an entry whose data is in the dir
+    /// file (archive index `0x7fff`), `archive_offset` has already been made relative to the
+    /// start of the dir file rather than the end of the tree.  
+    /// This is called before the entry is inserted, so a duplicate entry is visited again.
+    fn visit(&self, ext: &Ext<'_>, dir: &[u8], filename: &[u8], entry: &VPKDirectoryEntry);
+}
+impl<F> ReadVisitor for F
+where
+    F: Fn(&Ext<'_>, &[u8], &[u8], &VPKDirectoryEntry) + Send + Sync,
+{
+    fn visit(&self, ext: &Ext<'_>, dir: &[u8], filename: &[u8], entry: &VPKDirectoryEntry) {
+        self(ext, dir, filename, entry)
+    }
+}
+impl std::fmt::Debug for dyn ReadVisitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ReadVisitor")
+    }
+}
+
+/// See [`ReadOptions::on_duplicate`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnDuplicate {
+    /// Silently replace the earlier entry with the later one
+    #[default]
+    Overwrite,
+    /// Replace the earlier entry, and record it in [`VPK::duplicate_entries`]
+    Report,
+    /// Fail with [`Error::DuplicateEntry`].  
+    /// With [`ReadOptions::lenient`], the later entry is skipped instead and recorded in
+    /// [`VPK::skipped_entries`], keeping the earlier one.
+    Error,
+}
+
+/// An entry that was replaced by a later one with the same path, when reading with
+/// [`OnDuplicate::Report`]
+#[derive(Debug, Clone)]
+pub struct DuplicateEntry {
+    /// The position in the dir file of the filename of the later entry
+    pub position: usize,
+    /// The path of the entry, like `materials/brick/wall01.vtf`
+    pub path: String,
+    /// The earlier entry, which is no longer in the tree
+    pub replaced: VPKEntry,
+}
+
+/// An entry that was skipped when reading with [`ReadOptions::lenient`]
+#[derive(Debug, Clone)]
+pub struct SkippedEntry {
+    /// The position in the dir file where the problem was found
+    pub position: usize,
+    /// The path of the entry, like `materials/brick/wall01.vtf`.  
+    /// Empty if the problem wasn't with a specific entry.
+    pub path: String,
+    /// This is an `Arc` so that the [`VPK`] can still be cloned
+    pub error: Arc<Error>,
+}
+
+/// A VPK dir file and the tree of its entries.  
+/// `S` builds the hashers for the maps of the tree, see [`ReadOptions::hash_builder`].
 #[derive(Clone)]
-pub struct VPK {
+pub struct VPK<S = MapRandomState> {
     pub header_length: u32,
+    pub endian: Endian,
     pub header: VPKHeader,
     pub header_v2: Option<VPKHeaderV2>,
     pub header_v2_checksum: Option<VPKHeaderV2Checksum>,
-    tree: VPKTree,
+    pub(crate) tree: VPKTree<S>,
 
     /// The data in a dir is usually pretty small, so just keeping the loaded file
     /// is cheaper than reading out isolated preload data vecs and the like.
     pub(crate) data: Arc<[u8]>,
-    pub archive_paths: Vec<String>,
+    /// The path of each archive file, indexed by archive index.  
+    /// Only archives that an entry is stored in have a path, other indices up to the largest one
+    /// are `None`. See [`VPK::archive_path`].
+    pub archive_paths: Vec<Option<Arc<str>>>,
+    /// The modified time of the dir file when it was read
+    source_modified: Option<SystemTime>,
+    /// The path the dir file was read from, if it was read from a file
+    dir_path: Option<PathBuf>,
+    /// Whether the keys were normalized with [`ReadOptions::normalize_case`]
+    normalized_case: bool,
+    /// Only built with [`ReadOptions::store_full_paths`]
+    full_paths: Option<FullPaths>,
+    /// Only built with [`ReadOptions::build_sorted_tree`]
+    sorted: Option<SortedTree>,
+    /// Entries that could not be read, only filled when reading with
+    /// [`ReadOptions::lenient`]
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Entries that were replaced by a later one with the same path, only filled when reading
+    /// with [`OnDuplicate::Report`]
+    pub duplicate_entries: Vec<DuplicateEntry>,
+    /// Only filled for version 2 VPKs read with [`ReadOptions::keep_v2_regions`]
+    pub v2_regions: Option<VPKHeaderV2Regions>,
+    /// Only gathered with [`ReadOptions::record_stats`]
+    parse_stats: Option<ParseStats>,
+    /// The options this was read with, which [`VPK::reload_into`] reads the dir file with again
+    options: ReadOptions<S>,
 }
 
 impl VPK {
-    pub fn read(dir_path: &Path, probable_kind: ProbableKind) -> Result<VPK, Error> {
+    /// Read the VPK dir file at `dir_path`.  
+    /// ```rust,no_run
+    /// use vpk::vpk::ProbableKind;
+    /// use vpk::VPK;
+    ///
+    /// let vpk = VPK::read("tf/tf2_misc_dir.vpk", ProbableKind::Tf2Misc)?;
+    ///
+    /// let path = String::from("tf/tf2_textures_dir.vpk");
+    /// let vpk = VPK::read(&path, ProbableKind::Tf2Textures)?;
+    /// # Ok::<(), vpk::Error>(())
+    /// ```
+    pub fn read(dir_path: impl AsRef<Path>, probable_kind: ProbableKind) -> Result<VPK, Error> {
+        VPK::read_with_options(dir_path, &ReadOptions::new(probable_kind))
+    }
+
+    /// Read a VPK dir file that is already in memory.  
+    /// Since there's no path to find the archive files from, `archive_path` gives the path to the
+    /// archive file with the given index.
+    /// ```rust,no_run
+    /// use vpk::vpk::ProbableKind;
+    /// use vpk::VPK;
+    ///
+    /// let data = std::fs::read("pak01_dir.vpk")?;
+    /// let vpk = VPK::read_bytes(
+    ///     data,
+    ///     |i| format!("pak01_{:03}.vpk", i),
+    ///     ProbableKind::None,
+    /// )?;
+    /// # Ok::<(), vpk::Error>(())
+    /// ```
+    pub fn read_bytes(
+        data: impl Into<Arc<[u8]>>,
+        archive_path: impl Fn(u16) -> String,
+        probable_kind: ProbableKind,
+    ) -> Result<VPK, Error> {
+        VPK::read_bytes_with_options(data, archive_path, &ReadOptions::new(probable_kind))
+    }
+}
+
+impl<S: BuildHasher + Clone> VPK<S> {
+    /// Read the VPK dir file at `dir_path`, with more control over how it is read.  
+    /// ```rust,no_run
+    /// use vpk::vpk::ReadOptions;
+    /// use vpk::VPK;
+    ///
+    /// let options = ReadOptions {
+    ///     archive_dir: Some("content/".into()),
+    ///     ..ReadOptions::default()
+    /// };
+    /// let vpk = VPK::read_with_options("pak01_dir.vpk", &options)?;
+    /// # Ok::<(), vpk::Error>(())
+    /// ```
+    pub fn read_with_options(
+        dir_path: impl AsRef<Path>,
+        options: &ReadOptions<S>,
+    ) -> Result<VPK<S>, Error> {
+        let tree = options.empty_tree();
+        VPK::read_with_tree(dir_path.as_ref(), options, tree)
+    }
+
+    /// Read the dir file at `dir_path` again, replacing the contents of this VPK.  
+    /// It is read with the same [`ReadOptions`] as this VPK was, so that the archive paths and
+    /// lookups work the same way afterwards.  
+    /// The maps of the existing tree are cleared and reused rather than allocating new ones,
+    /// which is useful when repeatedly reloading a VPK that changes.  
+    /// If reading fails, then this VPK is left empty, as if it had been read from a dir file with
+    /// no entries.
+    pub fn reload_into(&mut self, dir_path: impl AsRef<Path>) -> Result<(), Error> {
+        let options = self.options.clone();
+        self.reload_into_with_options(dir_path, &options)
+    }
+
+    /// Like [`VPK::reload_into`], but with different options, which later reloads then use.  
+    /// [`ReadOptions::probable_kind`] and [`ReadOptions::hash_builder`] are ignored, since the
+    /// existing maps already have their capacity and hashers.
+    pub fn reload_into_with_options(
+        &mut self,
+        dir_path: impl AsRef<Path>,
+        options: &ReadOptions<S>,
+    ) -> Result<(), Error> {
+        let empty = VPKTree::new_with_hasher(ProbableKind::None, self.tree.hasher().clone());
+        let mut tree = mem::replace(&mut self.tree, empty);
+        tree.clear();
+
+        match VPK::read_with_tree(dir_path.as_ref(), options, tree) {
+            Ok(vpk) => {
+                *self = vpk;
+                Ok(())
+            }
+            Err(err) => {
+                // The old entries are already gone, so don't leave the rest of the old VPK behind
+                let empty =
+                    VPKTree::new_with_hasher(ProbableKind::None, self.tree.hasher().clone());
+                *self = VPK::empty(empty, options.clone());
+                Err(err)
+            }
+        }
+    }
+
+    /// A version 1 VPK with no entries, which is what [`VPK::reload_into`] leaves behind when it
+    /// fails.
+    fn empty(tree: VPKTree<S>, options: ReadOptions<S>) -> VPK<S> {
+        VPK {
+            header_length: 4 * 3,
+            endian: Endian::Little,
+            header: VPKHeader {
+                signature: VPK_SIGNATURE,
+                version: 1,
+                tree_length: 0,
+            },
+            header_v2: None,
+            header_v2_checksum: None,
+            tree,
+            data: Arc::from([]),
+            archive_paths: Vec::new(),
+            source_modified: None,
+            dir_path: None,
+            normalized_case: false,
+            full_paths: None,
+            sorted: None,
+            skipped_entries: Vec::new(),
+            duplicate_entries: Vec::new(),
+            v2_regions: None,
+            parse_stats: None,
+            options,
+        }
+    }
+
+    fn read_with_tree(
+        dir_path: &Path,
+        options: &ReadOptions<S>,
+        tree: VPKTree<S>,
+    ) -> Result<VPK<S>, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("vpk_read", path = %dir_path.display()).entered();
+
         // Read the file into memory. Dir vpks are usually pretty small.
         let file: Arc<[u8]> = Arc::from(std::fs::read(dir_path)?);
+        // Not every platform supports this, so it is only an error when it is asked for
+        let source_modified = std::fs::metadata(dir_path)?.modified().ok();
+
+        // TODO: don't require this to be a str? Weird systems might have bad utf8 in the paths
+        let dir_path = dir_path.to_str().unwrap();
+        let archive_dir = match options.archive_dir.as_deref() {
+            Some(archive_dir) => Some(
+                archive_dir
+                    .to_str()
+                    .ok_or_else(|| Error::NonUtf8ArchiveDir(archive_dir.to_path_buf()))?,
+            ),
+            None => None,
+        };
+        let archive_path = |i| archive_path_for(dir_path, archive_dir, i);
+
+        let mut vpk = match VPK::read_bytes_with_tree(file, &archive_path, options, tree) {
+            Ok(vpk) => vpk,
+            Err(Error::InvalidSignature) => {
+                // Numbered archives don't have a header, so this is an easy mistake to make
+                return Err(match numbered_archive_dir_path(dir_path) {
+                    Some(suggested) => Error::NumberedArchive {
+                        path: dir_path.to_string(),
+                        suggested,
+                    },
+                    None => Error::InvalidSignature,
+                });
+            }
+            Err(err) => return Err(err),
+        };
+        vpk.source_modified = source_modified;
+        vpk.dir_path = Some(PathBuf::from(dir_path));
+
+        Ok(vpk)
+    }
+
+    /// Read a VPK dir file that is already in memory, with more control over how it is read.  
+    /// [`ReadOptions::archive_dir`] is ignored, since `archive_path` decides where the archive
+    /// files are.
+    pub fn read_bytes_with_options(
+        data: impl Into<Arc<[u8]>>,
+        archive_path: impl Fn(u16) -> String,
+        options: &ReadOptions<S>,
+    ) -> Result<VPK<S>, Error> {
+        let tree = options.empty_tree();
+        VPK::read_bytes_with_tree(data.into(), &archive_path, options, tree)
+    }
+
+    /// Parse the dir file data of `existing` again with different options, without reading the
+    /// file again. The data is shared between both, so this only allocates the new tree.  
+    /// The archive paths are copied from `existing`, so [`ReadOptions::archive_dir`] is ignored.
+    /// Since the data is the same, only a different [`ReadOptions::start_offset`] could find
+    /// entries with archive indices past them, which get a path derived from the path `existing`
+    /// was read from, or an empty path if it was read from memory.  
+    /// Note that the data is what is left after the `start_offset` `existing` was read with.
+    pub fn reparse_from<T>(existing: &VPK<T>, options: &ReadOptions<S>) -> Result<VPK<S>, Error> {
+        let dir_path = existing.dir_path.as_deref().and_then(Path::to_str);
+        let archive_path = |i: u16| match existing.archive_path(i) {
+            Some(path) => path.to_string(),
+            None => {
+                dir_path.map_or_else(String::new, |dir_path| archive_path_for(dir_path, None, i))
+            }
+        };
+
+        let tree = options.empty_tree();
+        let mut vpk =
+            VPK::read_bytes_with_tree(existing.data.clone(), &archive_path, options, tree)?;
+        vpk.source_modified = existing.source_modified;
+        vpk.dir_path.clone_from(&existing.dir_path);
 
+        Ok(vpk)
+    }
+
+    fn read_bytes_with_tree(
+        file: Arc<[u8]>,
+        archive_path: &dyn Fn(u16) -> String,
+        options: &ReadOptions<S>,
+        tree: VPKTree<S>,
+    ) -> Result<VPK<S>, Error> {
+        let start = Instant::now();
+        let file = match options.start_offset {
+            0 => file,
+            start => match file.get(start..) {
+                // Copying the rest is simpler than keeping the offset around for every access
+                Some(rest) => Arc::from(rest),
+                None => return Err(Error::RegionOutOfBounds(start..file.len())),
+            },
+        };
+        let endian = match options.endian {
+            Some(endian) => endian,
+            None => detect_endian(&file),
+        };
+        let file = decompress_tree(
+            file,
+            endian,
+            options.tree_compression,
+            options.max_tree_size,
+        )?;
         let mut reader = Cursor::new(file.as_ref());
 
+        #[cfg(feature = "tracing")]
+        let header_span = tracing::debug_span!("header").entered();
+
         // Read main VPK header
-        let header: VPKHeader = VPKHeader::read_le(&mut reader)?;
+        let header: VPKHeader = VPKHeader::read(&mut reader, endian)?;
 
         if header.signature != VPK_SIGNATURE {
             return Err(Error::InvalidSignature);
@@ -227,81 +999,233 @@ impl VPK {
 
         let mut vpk = VPK {
             header_length: 4 * 3,
+            endian,
             header,
             header_v2: None,
             header_v2_checksum: None,
-            tree: VPKTree::new_with_capacity(probable_kind),
+            tree,
             data: file.clone(),
             archive_paths: Vec::new(),
+            source_modified: None,
+            dir_path: None,
+            normalized_case: options.normalize_case,
+            full_paths: None,
+            sorted: None,
+            skipped_entries: Vec::new(),
+            duplicate_entries: Vec::new(),
+            v2_regions: None,
+            parse_stats: None,
+            options: options.clone(),
         };
 
         if vpk.header.version == 2 {
-            let header_v2 = VPKHeaderV2::read_le(&mut reader)?;
+            let header_v2 = VPKHeaderV2::read(&mut reader, endian)?;
 
-            if header_v2.self_hashes_length != VPK_SELF_HASHES_LENGTH {
+            // Some tools write other lengths, which is fine as long as the regions still fit.
+            // The checksums are only read if there is room for them.
+            if header_v2.self_hashes_length != VPK_SELF_HASHES_LENGTH && !options.lenient {
                 return Err(Error::HashSizeMismatch);
             }
+            let has_checksum = header_v2.self_hashes_length >= VPK_SELF_HASHES_LENGTH;
             vpk.header_length += 4 * 4;
 
-            let checksum_offset: u32 = vpk.header.tree_length
-                + header_v2.embed_chunk_length
-                + header_v2.chunk_hashes_length;
-            reader.seek(SeekFrom::Current(checksum_offset as i64))?;
-
-            let header_v2_checksum = VPKHeaderV2Checksum::read_le(&mut reader)?;
+            // Check that the regions are within the data, so that the checksums can be read and
+            // everything using the layout later can rely on it.
+            // The checksums are at the start of the self hashes, which has room for them
+            let layout =
+                VPKHeaderV2Layout::try_new(vpk.header_length, &vpk.header, &header_v2, file.len())?;
+            reader.seek(SeekFrom::Start(layout.self_hashes.start as u64))?;
+            if options.keep_v2_regions {
+                vpk.v2_regions = VPKHeaderV2Regions::new(&file, &layout);
+            }
 
+            if has_checksum {
+                vpk.header_v2_checksum = Some(VPKHeaderV2Checksum::read_le(&mut reader)?);
+            }
             vpk.header_v2 = Some(header_v2);
-            vpk.header_v2_checksum = Some(header_v2_checksum);
 
             // Return seek to initial position - after header
             let header_length = mem::size_of::<VPKHeader>() + mem::size_of::<VPKHeaderV2>();
             reader.seek(SeekFrom::Start(header_length as u64))?;
         }
 
+        #[cfg(feature = "tracing")]
+        drop(header_span);
+
+        // The data that the `DirFile` keys point into
+        let key_data: Arc<[u8]> = if options.normalize_case {
+            // Only the dirs and filenames of this are ever looked at, so we can just normalize
+            // the whole thing
+            file.iter()
+                .map(|&b| access::normalize_path_byte(b))
+                .collect()
+        } else {
+            file.clone()
+        };
+
         // Read index tree
-        // let mut avg_name = 0.0;
-        // let mut name_count = 0;
+        // In lenient mode, an error that stops us from reading the rest of the tree still keeps
+        // all of the entries read before it.
+        let tree_res = VPK::read_tree(
+            &mut vpk,
+            &mut reader,
+            &file,
+            &key_data,
+            archive_path,
+            options,
+        );
+        if let Err(error) = tree_res {
+            // Say where it stopped, so that it can be found in the file
+            let error = match error {
+                Error::ReadError(error) => Error::TreeReadError {
+                    position: reader.position() as usize,
+                    error,
+                },
+                error => error,
+            };
+            if !options.lenient {
+                return Err(error);
+            }
+
+            vpk.skipped_entries.push(SkippedEntry {
+                position: reader.position() as usize,
+                path: String::new(),
+                error: Arc::new(error),
+            });
+        }
 
-        // let mut avg_path = 0.0;
-        // let mut path_count = 0;
+        if options.validate_archives {
+            let missing_archives: Vec<_> = vpk
+                .archive_paths
+                .iter()
+                .flatten()
+                .filter(|path| !Path::new(&***path).is_file())
+                .map(|path| path.to_string())
+                .collect();
+            if !missing_archives.is_empty() {
+                return Err(Error::MissingArchives(missing_archives));
+            }
+        }
+
+        if options.store_full_paths {
+            vpk.full_paths = Some(FullPaths::new(&vpk));
+        }
 
-        // let mut avg_ext = 0.0;
-        // let mut ext_count = 0;
+        if options.build_sorted_tree {
+            vpk.sorted = Some(vpk.sorted_tree());
+        }
 
-        // let mut avg_path_count = 0.0;
-        // let mut path_count_count = 0;
+        if options.record_stats {
+            let summary = vpk.summary();
+            vpk.parse_stats = Some(ParseStats {
+                duration: start.elapsed(),
+                per_ext_counts: summary
+                    .ext_counts
+                    .into_iter()
+                    .map(|(ext, count)| (ext.into_owned(), count))
+                    .collect(),
+                total_bytes: summary.total_size,
+            });
+        }
+
+        debug_assert!(
+            vpk.tree.misplaced_other_exts().next().is_none(),
+            "Entries were put in the `other` map rather than the map for their extension"
+        );
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            archives = vpk.archive_paths.iter().flatten().count(),
+            skipped = vpk.skipped_entries.len(),
+            malformed = vpk.malformed_entry_count(),
+            "read vpk"
+        );
+
+        Ok(vpk)
+    }
+
+    /// Read the entries of the tree into `vpk`, leaving `reader` where it stopped if it fails.
+    fn read_tree(
+        vpk: &mut VPK<S>,
+        reader: &mut Cursor<&[u8]>,
+        file: &[u8],
+        key_data: &Arc<[u8]>,
+        archive_path: &dyn Fn(u16) -> String,
+        options: &ReadOptions<S>,
+    ) -> Result<(), Error> {
+        let endian = vpk.endian;
+        // The entries stored so far, including ones that replaced a duplicate
+        let mut entry_count = 0usize;
+        // Dir bytes -> the first range they were found at, only used if we're interning dirs
+        let mut interned_dirs: HashMap<&[u8], Range<usize>, S> =
+            HashMap::with_hasher(options.hash_builder.clone());
+        // The tree is terminated by an empty ext, but some writers leave that off and instead
+        // just end the tree, sometimes without ending the last dir and path either. Others pad the tree with nulls after the terminator, which we never
+        // look at since we stop at the first empty ext.
+        let tree_end = u64::from(vpk.header_length) + u64::from(vpk.header.tree_length);
+        // The data of entries stored in the dir file is somewhere after the tree
+        let inline_length = (file.len() as u64).saturating_sub(tree_end);
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("tree", tree_length = vpk.header.tree_length).entered();
 
-        // TODO: don't require this to be a str? Weird systems might have bad utf8 in the paths
-        let dir_path = dir_path.to_str().unwrap();
-        // The largest archive index, used to initialize the archive paths vec
-        let mut max_archive_index = 0;
         loop {
-            // let ext_start = std::time::Instant::now();
-            let ext = read_cstring(&mut reader)?;
+            if reader.position() >= tree_end {
+                break;
+            }
+
+            let ext = read_cstring(reader)?;
             if ext.is_empty() {
                 break;
             }
 
             let ext = Ext::from_ext_slice(ext);
+            let skip_ext = options
+                .ext_filter
+                .as_ref()
+                .is_some_and(|filter| !filter.contains(&ext));
+            #[cfg(feature = "tracing")]
+            let _span = tracing::trace_span!(
+                "ext",
+                ext = %String::from_utf8_lossy(ext.as_slice())
+            )
+            .entered();
 
-            // let mut p_count = 0;
             loop {
-                // let path_start = std::time::Instant::now();
+                if reader.position() >= tree_end {
+                    break;
+                }
 
-                let path = skip_cstring(&mut reader)?;
+                let mut path = skip_cstring(reader)?;
                 if path.is_empty() {
                     break;
                 }
 
-                // p_count += 1;
+                if options.trim_paths {
+                    path = trim_path_end(file, path);
+                }
+
+                if options.intern_dirs {
+                    path = interned_dirs
+                        .entry(&file[path.clone()])
+                        .or_insert(path)
+                        .clone();
+                }
 
                 loop {
-                    // let name_start = std::time::Instant::now();
-                    let name = skip_cstring(&mut reader)?;
+                    if reader.position() >= tree_end {
+                        break;
+                    }
+
+                    let mut name = skip_cstring(reader)?;
                     if name.is_empty() {
                         break;
                     }
 
+                    if options.trim_paths {
+                        name = trim_path_end(file, name);
+                    }
+
                     // TODO: it might be possible to instead not do any str conversion
                     // and use the `&str`, or rather perhaps some reference into `&data`
                     // to avoid the conversion + allocation when this is initialized.
@@ -310,68 +1234,184 @@ impl VPK {
                     // `DirFile` and also for comparison..
                     // let name = name.to_lowercase();
 
-                    let mut dir_entry = VPKDirectoryEntry::read_le(&mut reader)?;
+                    let mut dir_entry = VPKDirectoryEntry::read(reader, endian)?;
+
+                    if let Err(_err) = dir_entry.validate() {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!(error = %_err, "malformed entry");
+                        let error = Error::MalformedIndex {
+                            position: reader.position() as usize - VPKDirectoryEntry::SIZE,
+                        };
+                        if !options.lenient {
+                            return Err(error);
+                        }
+
+                        // The rest of the entry can't be trusted, but the preload length is
+                        // our best guess at where the next entry starts.
+                        vpk.push_skipped(
+                            options.max_entries,
+                            SkippedEntry {
+                                position: name.start,
+                                path: entry_path_lossy(file, &ext, path.clone(), name),
+                                error: Arc::new(error),
+                            },
+                        )?;
+                        reader.seek(SeekFrom::Current(dir_entry.preload_length as i64))?;
+                        continue;
+                    }
+
+                    if skip_ext {
+                        reader.seek(SeekFrom::Current(dir_entry.preload_length as i64))?;
+                        continue;
+                    }
+
+                    // Otherwise reading the preload data would panic
+                    // This can't be >usize becuase we're reading from a vec
+                    let preload_start = reader.position() as usize;
+                    let preload =
+                        preload_start..preload_start + usize::from(dir_entry.preload_length);
+                    if preload.end > file.len() {
+                        let error = Error::PreloadOutOfBounds {
+                            path: entry_path_lossy(file, &ext, path.clone(), name.clone()),
+                            preload,
+                        };
+                        if !options.lenient {
+                            return Err(error);
+                        }
 
-                    if dir_entry.suffix != 0xffff {
-                        return Err(Error::MalformedIndex);
+                        // There's no telling where the next entry starts, so guess that the
+                        // preload length is what's wrong and that there is no preload data.
+                        vpk.push_skipped(
+                            options.max_entries,
+                            SkippedEntry {
+                                position: name.start,
+                                path: entry_path_lossy(file, &ext, path.clone(), name),
+                                error: Arc::new(error),
+                            },
+                        )?;
+                        continue;
                     }
 
                     if dir_entry.archive_index == 0x7fff {
-                        dir_entry.archive_offset += vpk.header_length + vpk.header.tree_length;
+                        // Computed as a u64 since it can't overflow that, unlike the u32
+                        let start = tree_end + u64::from(dir_entry.archive_offset);
+                        let inline_error = || {
+                            let start = usize::try_from(start).unwrap_or(usize::MAX);
+                            Error::InlineDataOutOfBounds {
+                                path: entry_path_lossy(file, &ext, path.clone(), name.clone()),
+                                inline: start..start.saturating_add(dir_entry.file_length as usize),
+                            }
+                        };
+                        let absolute = dir_entry
+                            .validate_inline(inline_length)
+                            .ok()
+                            .and_then(|()| u32::try_from(start).ok());
+                        let Some(absolute) = absolute else {
+                            let error = inline_error();
+                            if !options.lenient {
+                                return Err(error);
+                            }
+
+                            // Only the data is out of bounds, so the next entry is still
+                            // right after the preload data
+                            vpk.push_skipped(
+                                options.max_entries,
+                                SkippedEntry {
+                                    position: name.start,
+                                    path: entry_path_lossy(file, &ext, path.clone(), name),
+                                    error: Arc::new(error),
+                                },
+                            )?;
+                            reader.seek(SeekFrom::Current(dir_entry.preload_length as i64))?;
+                            continue;
+                        };
+                        dir_entry.archive_offset = absolute;
                     }
 
                     // Ensure that our archive path is in the archive paths vec
-                    max_archive_index = max_archive_index.max(dir_entry.archive_index);
+                    if dir_entry.archive_index != 0x7fff {
+                        let index = usize::from(dir_entry.archive_index);
+                        if index >= vpk.archive_paths.len() {
+                            vpk.archive_paths.resize(index + 1, None);
+                        }
+                        if vpk.archive_paths[index].is_none() {
+                            let path = archive_path(dir_entry.archive_index);
+                            vpk.archive_paths[index] = Some(match &options.archive_path_cache {
+                                Some(cache) => cache.intern(path),
+                                None => Arc::from(path),
+                            });
+                        }
+                    }
 
                     let vpk_entry = VPKEntry {
                         dir_entry,
-                        // This can't be >usize becuase we're reading from a vec
-                        preload_start: reader.position() as usize,
+                        preload_start,
                     };
 
                     reader.seek(SeekFrom::Current(dir_entry.preload_length as i64))?;
 
-                    vpk.tree
-                        .insert(file.clone(), &ext, path.clone(), name, vpk_entry);
-
-                    // let name_end = std::time::Instant::now();
-                    // let name_time = name_end - name_start;
-                    // name_count += 1;
-                    // avg_name += (name_time.as_micros() as f32 - avg_name) / name_count as f32;
-                }
+                    if let Some(max) = options.max_entries {
+                        if entry_count >= max {
+                            return Err(Error::TooManyEntries { max });
+                        }
+                    }
+                    entry_count += 1;
 
-                // let path_end = std::time::Instant::now();
-                // let path_time = path_end - path_start;
-                // path_count += 1;
-                // avg_path += (path_time.as_micros() as f32 - avg_path) / path_count as f32;
+                    if let Some(visitor) = &options.visitor {
+                        visitor.visit(&ext, &file[path.clone()], &file[name.clone()], &dir_entry);
+                    }
 
-                // path_count_count += 1;
-                // avg_path_count += (p_count as f32 - avg_path_count) / path_count_count as f32;
+                    let replaced = vpk.tree.insert(
+                        key_data.clone(),
+                        &ext,
+                        path.clone(),
+                        name.clone(),
+                        vpk_entry,
+                    );
+                    if let Some(replaced) = replaced {
+                        let entry_path = entry_path_lossy(file, &ext, path.clone(), name.clone());
+                        match options.on_duplicate {
+                            OnDuplicate::Overwrite => {}
+                            OnDuplicate::Report => {
+                                vpk.duplicate_entries.push(DuplicateEntry {
+                                    position: name.start,
+                                    path: entry_path,
+                                    replaced,
+                                });
+                            }
+                            OnDuplicate::Error if options.lenient => {
+                                // Put the earlier entry back, so that it's the later one
+                                // that is skipped
+                                vpk.tree.insert(
+                                    key_data.clone(),
+                                    &ext,
+                                    path.clone(),
+                                    name.clone(),
+                                    replaced,
+                                );
+                                vpk.push_skipped(
+                                    options.max_entries,
+                                    SkippedEntry {
+                                        position: name.start,
+                                        path: entry_path.clone(),
+                                        error: Arc::new(Error::DuplicateEntry { path: entry_path }),
+                                    },
+                                )?;
+                            }
+                            OnDuplicate::Error => {
+                                return Err(Error::DuplicateEntry { path: entry_path });
+                            }
+                        }
+                    }
+                }
             }
-
-            // let ext_end = std::time::Instant::now();
-            // let ext_time = ext_end - ext_start;
-            // ext_count += 1;
-            // avg_ext += (ext_time.as_micros() as f32 - avg_ext) / ext_count as f32;
-        }
-
-        // eprintln!("avg_ext: {} ms", avg_ext / 1000.0);
-        // // microseconds
-        // eprintln!("avg_path {}", avg_path);
-        // eprintln!("avg_name {}", avg_name);
-
-        // eprintln!("avg_path_count {}", avg_path_count);
-
-        // Initialize the archive paths
-        vpk.archive_paths.reserve(max_archive_index as usize + 1);
-        for i in 0..=max_archive_index {
-            let archive_path = dir_path.replace("dir.", &format!("{:03}.", i));
-            vpk.archive_paths.push(archive_path);
         }
 
-        Ok(vpk)
+        Ok(())
     }
+}
 
+impl<S> VPK<S> {
     /// Get the path to an archive file.  
     /// ```rust,ignore
     /// let vpk: VPK = /* ... */;
@@ -386,39 +1426,115 @@ impl VPK {
     /// (Or, you could just use `entry.archive_path()`, since the handle already has the [`VPK`])
     pub fn archive_path(&self, archive_index: u16) -> Option<&str> {
         self.archive_paths
-            .get(usize::from(archive_index))
-            .map(String::as_str)
+            .get(usize::from(archive_index))?
+            .as_deref()
+    }
+
+    /// The path of the dir file, as it was given to [`VPK::read`].  
+    /// Returns `None` if the VPK was read from memory with [`VPK::read_bytes`].
+    pub fn dir_path(&self) -> Option<&Path> {
+        self.dir_path.as_deref()
+    }
+
+    /// The number of entries that were skipped with [`ReadOptions::lenient`] because they were
+    /// malformed, like having a suffix other than `0xffff`.  
+    /// One or two is likely a single damaged entry, while many usually means that the whole tree
+    /// is corrupted. The [`SkippedEntry::position`]s show where in the dir file they are.
+    pub fn malformed_entry_count(&self) -> usize {
+        self.skipped_entries
+            .iter()
+            .filter(|skipped| matches!(*skipped.error, Error::MalformedIndex { .. }))
+            .count()
+    }
+
+    /// Record an entry that was skipped with [`ReadOptions::lenient`], failing once there are
+    /// more than `max_entries` of them so that a corrupt tree can't grow the list without bound.
+    fn push_skipped(
+        &mut self,
+        max_entries: Option<usize>,
+        skipped: SkippedEntry,
+    ) -> Result<(), Error> {
+        if let Some(max) = max_entries {
+            if self.skipped_entries.len() >= max {
+                return Err(Error::TooManyEntries { max });
+            }
+        }
+
+        self.skipped_entries.push(skipped);
+        Ok(())
+    }
+
+    /// Whether every entry can be read from the dir file alone, so that no archive files are
+    /// needed, like in a sandbox without a filesystem.  
+    /// This is true if every entry is stored in the dir file, as preload data or in the embed
+    /// chunk. Entries that name an archive file but have no data in it don't need it either.
+    pub fn is_self_contained(&self) -> bool {
+        self.entries().all(|(_, entry)| {
+            entry.dir_entry.archive_index == 0x7fff || entry.dir_entry.file_length == 0
+        })
+    }
+
+    /// The modified time of the dir file, as of when it was read.  
+    /// VPK entries don't have their own times, so this is the closest thing to one.
+    pub fn source_modified(&self) -> std::io::Result<SystemTime> {
+        self.source_modified.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Modified time is not available on this platform",
+            )
+        })
     }
 
     /// Open every single archive path available as files.  
-    pub fn open_all_archive_paths(&self) -> std::io::Result<Vec<File>> {
+    /// Like [`VPK::archive_paths`], indices that no entry is stored in are `None`.
+    pub fn open_all_archive_paths(&self) -> std::io::Result<Vec<Option<File>>> {
         let mut files = Vec::with_capacity(self.archive_paths.len());
         for path in &self.archive_paths {
-            files.push(File::open(path)?);
+            files.push(path.as_deref().map(File::open).transpose()?);
         }
 
         Ok(files)
     }
 
+    /// Get a provider which opens each archive file when it is first read from and keeps it open,
+    /// for use with [`VPKEntryHandle::get_with_files`] and similar.  
+    /// Prefer this over [`VPKEntryHandle::get`] when reading many entries, as that opens the
+    /// archive file anew for every entry.
+    pub fn lazy_archives(&self) -> LazyArchives<'_, S> {
+        LazyArchives::new(self)
+    }
+}
+
+impl<S: BuildHasher> VPK<S> {
     pub fn get_direct<'s, K: Equivalent<DirFile> + Hash>(
         &'s self,
         ext: &Ext<'_>,
         re: K,
-    ) -> Option<VPKEntryHandle<'s>> {
+    ) -> Option<VPKEntryHandle<'s, S>> {
         self.tree
-            .get_direct(ext, re)
-            .map(|entry| VPKEntryHandle { vpk: self, entry })
+            .get_entry_direct(ext, re)
+            .map(|(ext, dir_file, entry)| VPKEntryHandle {
+                vpk: self,
+                ext,
+                dir_file,
+                entry,
+            })
     }
 
+    /// Get the entry, where `dir` and `filename` should be lowercase.  
+    /// If the VPK was read with [`ReadOptions::normalize_case`], then they are compared exactly,
+    /// which is faster.
     pub fn get<'s>(
         &'s self,
         ext: &Ext<'_>,
         dir: &str,
         filename: &str,
-    ) -> Option<VPKEntryHandle<'s>> {
-        self.tree
-            .get(ext, dir, filename)
-            .map(|entry| VPKEntryHandle { vpk: self, entry })
+    ) -> Option<VPKEntryHandle<'s, S>> {
+        if self.normalized_case {
+            self.get_direct(ext, DirFileBigRefExact::new(dir, filename))
+        } else {
+            self.get_direct(ext, DirFileBigRef::new(dir, filename))
+        }
     }
 
     pub fn get_ignore_case<'s>(
@@ -426,26 +1542,568 @@ impl VPK {
         ext: &Ext<'_>,
         dir: &str,
         filename: &str,
-    ) -> Option<VPKEntryHandle<'s>> {
-        self.tree
-            .get_ignore_case(ext, dir, filename)
-            .map(|entry| VPKEntryHandle { vpk: self, entry })
+    ) -> Option<VPKEntryHandle<'s, S>> {
+        self.get_direct(ext, DirFileBigRefLowercase::new(dir, filename))
     }
-}
 
-impl std::fmt::Debug for VPK {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    /// Like [`VPK::get`], but also gives the [`DirFile`] key, which has the dir and filename as
+    /// they are stored in the VPK.
+    pub fn get_entry<'s>(
+        &'s self,
+        ext: &Ext<'_>,
+        dir: &str,
+        filename: &str,
+    ) -> Option<(&'s DirFile, VPKEntryHandle<'s, S>)> {
+        let handle = self.get(ext, dir, filename)?;
+        Some((handle.dir_file, handle))
+    }
+
+    /// Like [`VPK::get_ignore_case`], but also gives the [`DirFile`] key, which has the dir and
+    /// filename as they are stored in the VPK.
+    pub fn get_entry_ignore_case<'s>(
+        &'s self,
+        ext: &Ext<'_>,
+        dir: &str,
+        filename: &str,
+    ) -> Option<(&'s DirFile, VPKEntryHandle<'s, S>)> {
+        let handle = self.get_ignore_case(ext, dir, filename)?;
+        Some((handle.dir_file, handle))
+    }
+
+    /// Get the entry at `index` in the map for `ext`, in the order they were read.  
+    /// This is useful for only looking at a window of entries, like in a list UI.
+    pub fn entry_at(&self, ext: &Ext<'_>, index: usize) -> Option<(&DirFile, &VPKEntry)> {
+        self.tree.for_ext(ext)?.get_index(index)
+    }
+
+    /// Iterate over every entry whose filename (not including the dir or extension) contains
+    /// `needle`.  
+    /// If `ext` is given, then only entries with that extension are searched, which avoids
+    /// scanning the other extensions entirely.
+    pub fn find_by_name<'s>(
+        &'s self,
+        needle: &str,
+        case_insensitive: bool,
+        ext: Option<&Ext<'_>>,
+    ) -> impl Iterator<Item = (Ext<'s>, &'s DirFile, &'s VPKEntry)> + 's {
+        let needle = needle.as_bytes().to_vec();
+        // Either every map, or only the one for `ext`
+        let all = ext.is_none().then(|| self.tree.maps());
+        let only = ext.and_then(|ext| self.tree.ext_map(ext));
+        all.into_iter()
+            .flatten()
+            .chain(only)
+            .flat_map(move |(ext, map)| {
+                let needle = needle.clone();
+                map.iter()
+                    .filter(move |(dir_file, _)| {
+                        contains_bytes(dir_file.filename(), &needle, case_insensitive)
+                    })
+                    .map(move |(dir_file, entry)| (ext.clone(), dir_file, entry))
+            })
+    }
+}
+
+impl<S> VPK<S> {
+    /// The tree of entries, for the lower level lookups like [`VPKTree::getf`] that don't need
+    /// a [`VPKEntryHandle`].
+    pub fn tree(&self) -> &VPKTree<S> {
+        &self.tree
+    }
+
+    /// The [`SortedTree`] built while reading with [`ReadOptions::build_sorted_tree`]
+    pub fn sorted(&self) -> Option<&SortedTree> {
+        self.sorted.as_ref()
+    }
+
+    /// The [`ParseStats`] gathered while reading with [`ReadOptions::record_stats`].  
+    /// The counts per extension are useful for picking a [`ProbableKind`] that reserves the right
+    /// capacities for a kind of VPK.
+    pub fn parse_stats(&self) -> Option<&ParseStats> {
+        self.parse_stats.as_ref()
+    }
+
+    /// Iterate over every entry in the VPK, regardless of extension.
+    pub fn entries(&self) -> Entries<'_, S> {
+        self.tree.iter()
+    }
+
+    /// Get every entry along with its extension, in the order it appears in the dir file.  
+    /// The [`DirFile`]s have the dir/filename as they are stored in the dir file, so this can be
+    /// used to reproduce the tree. The extension is lowercased, as it is for lookups, and this has
+    /// to sort all of the entries.  
+    /// The tree can only be reproduced exactly if it was read without
+    /// [`ReadOptions::normalize_case`] or [`ReadOptions::trim_paths`], which change the dir and
+    /// filename, and without duplicate entries, since only one of each is kept.
+    pub fn entries_in_file_order(&self) -> Vec<(Ext<'_>, &DirFile, &VPKEntry)> {
+        let mut entries: Vec<_> = self.tree.iter_with_ext().collect();
+        entries.sort_unstable_by_key(|(_, _, entry)| entry.preload_start);
+
+        entries
+    }
+
+    /// Iterate over every entry sorted by `(archive_index, archive_offset)`, so that each archive
+    /// file is read front-to-back when processing every entry.  
+    /// Entries stored in the dir file itself (archive index `0x7fff`) come last. This has to sort
+    /// references to all of the entries up front.
+    pub fn iter_sorted_by_archive(&self) -> impl Iterator<Item = (&DirFile, &VPKEntry)> {
+        let mut entries: Vec<_> = self.entries().collect();
+        entries.sort_unstable_by_key(|(_, entry)| {
+            (
+                entry.dir_entry.archive_index,
+                entry.dir_entry.archive_offset,
+            )
+        });
+
+        entries.into_iter()
+    }
+
+    /// Find every pair of entries whose data overlaps in the same archive file, which a valid VPK
+    /// shouldn't have. This is useful for catching bugs in whatever wrote the VPK, or tampering.  
+    /// Entries with exactly the same range are reported as [`OverlapKind::Duplicate`], since a
+    /// writer may do that on purpose to deduplicate identical files. Entries stored in the dir file
+    /// and entries with no archive data are ignored.  
+    /// The pairs are ordered by archive index and then offset.
+    pub fn find_overlaps<'s>(&'s self) -> Vec<EntryOverlap<'s, S>> {
+        let mut entries: Vec<_> = self
+            .tree
+            .iter_with_ext()
+            .filter(|(_, _, entry)| {
+                entry.dir_entry.archive_index != 0x7fff && entry.dir_entry.file_length != 0
+            })
+            .collect();
+        entries.sort_unstable_by_key(|(_, _, entry)| {
+            let dir_entry = &entry.dir_entry;
+            (
+                dir_entry.archive_index,
+                dir_entry.archive_offset,
+                dir_entry.file_length,
+            )
+        });
+
+        let range = |entry: &VPKEntry| {
+            let start = u64::from(entry.dir_entry.archive_offset);
+            start..start + u64::from(entry.dir_entry.file_length)
+        };
+        let handle =
+            |(ext, dir_file, entry): &(Ext<'s>, &'s DirFile, &'s VPKEntry)| VPKEntryHandle {
+                vpk: self,
+                ext: ext.clone(),
+                dir_file,
+                entry,
+            };
+
+        let mut overlaps = Vec::new();
+        // Indices of the earlier entries in the same archive that end after the current start
+        let mut active: Vec<usize> = Vec::new();
+        for (i, current) in entries.iter().enumerate() {
+            let current_range = range(current.2);
+            active.retain(|&j| {
+                let prev = entries[j].2;
+                prev.archive_index() == current.2.archive_index()
+                    && range(prev).end > current_range.start
+            });
+
+            for &j in &active {
+                let kind = if range(entries[j].2) == current_range {
+                    OverlapKind::Duplicate
+                } else {
+                    OverlapKind::Partial
+                };
+                overlaps.push(EntryOverlap {
+                    first: handle(&entries[j]),
+                    second: handle(current),
+                    kind,
+                });
+            }
+
+            active.push(i);
+        }
+
+        overlaps
+    }
+
+    /// Iterate over every entry along with its full path, like `materials/brick/wall01.vtf`.  
+    /// If the VPK was read with [`ReadOptions::store_full_paths`], then the paths are borrowed,
+    /// otherwise each one is built as it is needed. See [`VPKEntryHandle::full_path`].
+    pub fn iter_paths(&self) -> impl Iterator<Item = (Cow<'_, str>, VPKEntryHandle<'_, S>)> {
+        let mut stored = self.full_paths.as_ref().map(FullPaths::iter);
+        self.iter().map(move |handle| {
+            let path = match stored.as_mut().and_then(Iterator::next) {
+                Some(path) => Cow::Borrowed(path),
+                None => Cow::Owned(handle.full_path()),
+            };
+            (path, handle)
+        })
+    }
+
+    /// Iterate over every entry in the VPK along with its extension. See
+    /// [`VPKTree::iter_with_ext`].  
+    /// [`VPK::iter`] gives the same entries as [`VPKEntryHandle`]s, which can read the data.
+    pub fn iter_all(&self) -> impl Iterator<Item = (Ext<'_>, &DirFile, &VPKEntry)> {
+        self.tree.iter_with_ext()
+    }
+
+    /// Iterate over every entry in `dir_prefix` or any of its subdirectories, regardless of
+    /// extension.  
+    /// `dir_prefix` is like `models/weapons`, and is compared case-insensitively.
+    pub fn entries_by_dir<'s>(
+        &'s self,
+        dir_prefix: &'s str,
+    ) -> impl Iterator<Item = (Ext<'s>, &'s DirFile, &'s VPKEntry)> + 's {
+        let dir_prefix = dir_prefix.trim_end_matches(['/', '\\']).as_bytes();
+        self.tree.maps().flat_map(move |(ext, map)| {
+            map.iter()
+                .filter(move |(dir_file, _)| dir_has_prefix(dir_file.dir(), dir_prefix))
+                .map(move |(dir_file, entry)| (ext.clone(), dir_file, entry))
+        })
+    }
+
+    /// Iterate over every entry stored in the archive file with the given index, like `7` for
+    /// `pak01_007.vpk`. Entries stored in the dir file itself have the index `0x7fff`.  
+    /// The entries are in tree order, see [`VPK::iter_sorted_by_archive`] to read them in the
+    /// order they are in the archive.
+    pub fn entries_in_archive(
+        &self,
+        archive_index: u16,
+    ) -> impl Iterator<Item = (Ext<'_>, &DirFile, &VPKEntry)> {
+        self.iter_all()
+            .filter(move |(_, _, entry)| entry.dir_entry.archive_index == archive_index)
+    }
+
+    /// Iterate over a [`VPKEntryHandle`] for every entry in the VPK, so the data can be read
+    /// directly.
+    /// ```rust,ignore
+    /// for handle in vpk.iter() {
+    ///     let data = handle.get()?;
+    /// }
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = VPKEntryHandle<'_, S>> {
+        self.tree.maps().flat_map(move |(ext, map)| {
+            map.iter().map(move |(dir_file, entry)| VPKEntryHandle {
+                vpk: self,
+                ext: ext.clone(),
+                dir_file,
+                entry,
+            })
+        })
+    }
+
+    /// Get the `n` largest entries by [`VPKEntry::total_size`], largest first.
+    pub fn largest_entries(&self, n: usize) -> Vec<(&DirFile, &VPKEntry)> {
+        let mut entries: Vec<_> = self.entries().collect();
+        entries.sort_unstable_by_key(|(_, entry)| std::cmp::Reverse(entry.total_size()));
+        entries.truncate(n);
+
+        entries
+    }
+
+    /// A hash of the logical contents of the VPK: the path and CRC32 of every entry.  
+    /// This doesn't depend on the order of the entries or where their data is stored, so two VPKs
+    /// with the same files but different archive layouts have the same hash. Paths are compared
+    /// like [`VPK::get_ignore_case`], so casing and `\` separators don't change the hash either.  
+    /// This is an MD5 hash, so it is stable across versions and platforms, but isn't meant to
+    /// protect against tampering.
+    pub fn content_hash(&self) -> u128 {
+        let mut entries: Vec<_> = self
+            .tree
+            .maps()
+            .flat_map(|(ext, map)| {
+                map.iter().map(move |(dir_file, entry)| {
+                    let normalize =
+                        |v: &[u8]| v.iter().map(|&b| access::normalize_path_byte(b)).collect();
+                    let ext: Vec<u8> = normalize(ext.as_slice());
+                    let dir: Vec<u8> = normalize(dir_file.dir());
+                    let filename: Vec<u8> = normalize(dir_file.filename());
+                    (ext, dir, filename, entry.dir_entry.crc32)
+                })
+            })
+            .collect();
+        entries.sort_unstable();
+
+        let mut context = md5::Context::new();
+        for (ext, dir, filename, crc32) in &entries {
+            // Names can't contain null bytes, so they separate the fields unambiguously
+            for name in [ext, dir, filename] {
+                context.consume(name);
+                context.consume([0]);
+            }
+            context.consume(crc32.to_le_bytes());
+        }
+
+        u128::from_le_bytes(context.compute().0)
+    }
+
+    /// Get a short overview of the VPK, for when the [`Debug`] output is far too long.
+    /// ```rust,ignore
+    /// println!("{}", vpk.summary());
+    /// ```
+    pub fn summary(&self) -> VpkSummary<'_> {
+        let stats = self.stats();
+
+        VpkSummary {
+            version: self.header.version,
+            entry_count: stats.total.count,
+            ext_counts: stats
+                .per_ext
+                .into_iter()
+                .map(|(ext, ext_stats)| (ext, ext_stats.count))
+                .collect(),
+            archive_count: stats.archive_count,
+            total_size: stats.total.total_bytes,
+        }
+    }
+
+    /// Count the entries and their sizes for each extension, and for the whole VPK, in one pass
+    /// over the tree.
+    pub fn stats(&self) -> VpkStats<'_> {
+        let mut total = ExtStats::default();
+        let per_ext = self
+            .tree
+            .maps()
+            .filter(|(_, map)| !map.is_empty())
+            .map(|(ext, map)| {
+                let mut ext_stats = ExtStats::default();
+                for entry in map.values() {
+                    ext_stats.count += 1;
+                    ext_stats.total_bytes += entry.total_size();
+                    ext_stats.preload_bytes += u64::from(entry.dir_entry.preload_length);
+                }
+
+                total.count += ext_stats.count;
+                total.total_bytes += ext_stats.total_bytes;
+                total.preload_bytes += ext_stats.preload_bytes;
+                (ext, ext_stats)
+            })
+            .collect();
+
+        VpkStats {
+            per_ext,
+            total,
+            archive_count: self.archive_paths.iter().flatten().count(),
+        }
+    }
+
+    /// Get the layout of the regions in the dir file. Only version 2 VPKs have this.
+    pub fn v2_layout(&self) -> Option<VPKHeaderV2Layout> {
+        let header_v2 = self.header_v2.as_ref()?;
+        Some(VPKHeaderV2Layout::new(
+            self.header_length,
+            &self.header,
+            header_v2,
+        ))
+    }
+
+    /// Get the embedded chunk, which holds file data stored in the dir file itself after the tree.  
+    /// Returns `None` if this is not a version 2 VPK, or if the chunk goes past the end of the
+    /// file.
+    pub fn embed_chunk(&self) -> Option<&[u8]> {
+        let layout = self.v2_layout()?;
+        self.data.get(layout.embed_chunk)
+    }
+
+    /// Get the raw signature region at the end of the dir file, for replacing it when re-signing
+    /// a modified VPK.  
+    /// It is laid out as:
+    /// - the length of the public key, as a little endian `u32`
+    /// - the RSA public key, DER encoded
+    /// - the length of the signature, as a little endian `u32`
+    /// - the signature of everything in the file before this region
+    ///
+    /// Returns `None` if this is not a version 2 VPK, if it isn't signed, or if the region goes
+    /// past the end of the file.
+    pub fn signature_region(&self) -> Option<&[u8]> {
+        let layout = self.v2_layout()?;
+        if layout.signature.is_empty() {
+            return None;
+        }
+
+        self.data.get(layout.signature)
+    }
+
+    fn region(&self, range: Range<usize>) -> Result<&[u8], Error> {
+        self.data
+            .get(range.clone())
+            .ok_or(Error::RegionOutOfBounds(range))
+    }
+
+    /// Check the MD5 of the tree against [`VPKHeaderV2Checksum::tree_checksum`]
+    pub fn verify_tree_checksum(&self) -> Result<bool, Error> {
+        let (layout, checksum) = self.v2_checksum()?;
+        let tree = self.region(layout.tree)?;
+
+        Ok(md5_u128(tree) == checksum.tree_checksum)
+    }
+
+    /// Check the MD5 of the chunk hashes against
+    /// [`VPKHeaderV2Checksum::chunk_hashes_checksum`]
+    pub fn verify_chunk_hashes_checksum(&self) -> Result<bool, Error> {
+        let (layout, checksum) = self.v2_checksum()?;
+        let chunk_hashes = self.region(layout.chunk_hashes)?;
+
+        Ok(md5_u128(chunk_hashes) == checksum.chunk_hashes_checksum)
+    }
+
+    /// Check the MD5 of the dir file against [`VPKHeaderV2Checksum::file_checksum`].  
+    /// This covers everything in the file before the `file_checksum` itself, including the
+    /// other two checksums.
+    pub fn verify_file_checksum(&self) -> Result<bool, Error> {
+        let (layout, checksum) = self.v2_checksum()?;
+        // tree_checksum + chunk_hashes_checksum
+        let end = layout.self_hashes.start + 32;
+        let file = self.region(0..end)?;
+
+        Ok(md5_u128(file) == checksum.file_checksum)
+    }
+
+    /// Check all of the checksums in the dir file.  
+    /// Returns `Err(Error::NoChecksums)` if this is not a version 2 VPK.
+    pub fn verify_checksums(&self) -> Result<bool, Error> {
+        Ok(self.verify_tree_checksum()?
+            && self.verify_chunk_hashes_checksum()?
+            && self.verify_file_checksum()?)
+    }
+
+    fn v2_checksum(&self) -> Result<(VPKHeaderV2Layout, &VPKHeaderV2Checksum), Error> {
+        let header_v2 = self.header_v2.as_ref().ok_or(Error::NoChecksums)?;
+        let layout = VPKHeaderV2Layout::try_new(
+            self.header_length,
+            &self.header,
+            header_v2,
+            self.data.len(),
+        )?;
+        let checksum = self.header_v2_checksum.as_ref().ok_or(Error::NoChecksums)?;
+
+        Ok((layout, checksum))
+    }
+}
+
+impl<S: std::fmt::Debug> std::fmt::Debug for VPK<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("VPK")
             .field("header_length", &self.header_length)
+            .field("endian", &self.endian)
             .field("header", &self.header)
             .field("header_v2", &self.header_v2)
             .field("header_v2_checksum", &self.header_v2_checksum)
             .field("tree", &self.tree)
             .field("data", &self.data.len())
+            .field("dir_path", &self.dir_path)
             .finish()
     }
 }
 
+/// The full path of every entry, in the same order as [`VPK::iter`]
+#[derive(Debug, Clone)]
+struct FullPaths {
+    /// Every path, one after another
+    data: String,
+    /// The end of each path in `data`
+    ends: Vec<usize>,
+}
+impl FullPaths {
+    fn new<S>(vpk: &VPK<S>) -> FullPaths {
+        let mut data = String::new();
+        let mut ends = Vec::new();
+        for handle in vpk.iter() {
+            data.push_str(&handle.full_path());
+            ends.push(data.len());
+        }
+
+        FullPaths { data, ends }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &str> {
+        let mut start = 0;
+        self.ends.iter().map(move |&end| {
+            let path = &self.data[start..end];
+            start = end;
+            path
+        })
+    }
+}
+
+/// See [`VPK::find_overlaps`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapKind {
+    /// Both entries have exactly the same archive offset and length
+    Duplicate,
+    /// The entries share some of their data, but not all of it
+    Partial,
+}
+
+/// Two entries whose data overlaps in the same archive file. See [`VPK::find_overlaps`]
+#[derive(Debug)]
+pub struct EntryOverlap<'a, S = MapRandomState> {
+    /// The entry that starts first in the archive
+    pub first: VPKEntryHandle<'a, S>,
+    pub second: VPKEntryHandle<'a, S>,
+    pub kind: OverlapKind,
+}
+
+/// See [`VPK::parse_stats`]
+#[derive(Debug, Clone)]
+pub struct ParseStats {
+    /// How long parsing took, not including reading the file
+    pub duration: Duration,
+    /// The number of entries for each extension that has any
+    pub per_ext_counts: Vec<(Ext<'static>, usize)>,
+    /// The sum of [`VPKEntry::total_size`] for every entry
+    pub total_bytes: u64,
+}
+
+/// See [`VPK::stats`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VpkStats<'a> {
+    /// The stats of each extension that has any entries
+    pub per_ext: Vec<(Ext<'a>, ExtStats)>,
+    /// The stats of every entry together
+    pub total: ExtStats,
+    /// The number of archive files that entries are stored in
+    pub archive_count: usize,
+}
+
+/// The entries of one extension, or of the whole VPK, in [`VpkStats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExtStats {
+    pub count: usize,
+    /// The sum of [`VPKEntry::total_size`]
+    pub total_bytes: u64,
+    /// How much of `total_bytes` is preload data, stored in the dir file
+    pub preload_bytes: u64,
+}
+
+/// See [`VPK::summary`]
+#[derive(Debug, Clone)]
+pub struct VpkSummary<'a> {
+    pub version: u32,
+    pub entry_count: usize,
+    /// The number of entries for each extension that has any
+    pub ext_counts: Vec<(Ext<'a>, usize)>,
+    pub archive_count: usize,
+    /// The sum of [`VPKEntry::total_size`] for every entry
+    pub total_size: u64,
+}
+impl std::fmt::Display for VpkSummary<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "VPK v{}: {} entries, {} archives, {} bytes",
+            self.version, self.entry_count, self.archive_count, self.total_size
+        )?;
+        for (ext, count) in &self.ext_counts {
+            write!(
+                f,
+                "\n  {}: {}",
+                String::from_utf8_lossy(ext.as_slice()),
+                count
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
 // TODO: allow customization of indexmap hasher?
 
 // VPK Files are a tree three levels deep.
@@ -455,35 +2113,63 @@ impl std::fmt::Debug for VPK {
 
 /// The three level file tree of the VPK.
 /// You should use [`get`] to access.
-/// Exts/dirs/filenames are stored lowercase.
+/// Exts/dirs/filenames are stored lowercase.  
+/// `S` builds the hashers of the maps, see [`ReadOptions::hash_builder`].
 #[derive(Debug, Default, Clone)]
-pub struct VPKTree {
+pub struct VPKTree<S = MapRandomState> {
     // TODO: consider whether to lowercase all paths always
     // filename!
-    pub vmt: DirFileEntryMap,
-    pub vtf: DirFileEntryMap,
-    pub vtx: DirFileEntryMap,
-    pub vvd: DirFileEntryMap,
-    pub phy: DirFileEntryMap,
-    pub res: DirFileEntryMap,
-    pub mdl: DirFileEntryMap,
-    pub scr: DirFileEntryMap,
-    pub xsc: DirFileEntryMap,
-    pub gam: DirFileEntryMap,
-    pub lst: DirFileEntryMap,
-    pub dsp: DirFileEntryMap,
-    pub ico: DirFileEntryMap,
-    pub icns: DirFileEntryMap,
-    pub bmp: DirFileEntryMap,
-    pub dat: DirFileEntryMap,
-    pub wav: DirFileEntryMap,
-    pub mp3: DirFileEntryMap,
+    pub vmt: DirFileEntryMap<S>,
+    pub vtf: DirFileEntryMap<S>,
+    pub vtx: DirFileEntryMap<S>,
+    pub vvd: DirFileEntryMap<S>,
+    pub phy: DirFileEntryMap<S>,
+    pub res: DirFileEntryMap<S>,
+    pub mdl: DirFileEntryMap<S>,
+    pub scr: DirFileEntryMap<S>,
+    pub xsc: DirFileEntryMap<S>,
+    pub gam: DirFileEntryMap<S>,
+    pub lst: DirFileEntryMap<S>,
+    pub dsp: DirFileEntryMap<S>,
+    pub ico: DirFileEntryMap<S>,
+    pub icns: DirFileEntryMap<S>,
+    pub bmp: DirFileEntryMap<S>,
+    pub dat: DirFileEntryMap<S>,
+    pub wav: DirFileEntryMap<S>,
+    pub mp3: DirFileEntryMap<S>,
     /// (ext, dir file entry map)
-    pub other: IndexMap<Vec<u8>, DirFileEntryMap, access::MapRandomState>,
+    pub other: IndexMap<ExtBytes, DirFileEntryMap<S>, S>,
 }
 impl VPKTree {
     pub fn new_with_capacity(probable_kind: ProbableKind) -> VPKTree {
-        let mut tree = VPKTree::default();
+        VPKTree::new_with_hasher(probable_kind, MapRandomState::default())
+    }
+}
+impl<S: Clone> VPKTree<S> {
+    /// Like [`VPKTree::new_with_capacity`], but with every map using `hash_builder`.
+    pub fn new_with_hasher(probable_kind: ProbableKind, hash_builder: S) -> VPKTree<S> {
+        let map = || DirFileEntryMap::with_hasher(hash_builder.clone());
+        let mut tree = VPKTree {
+            vmt: map(),
+            vtf: map(),
+            vtx: map(),
+            vvd: map(),
+            phy: map(),
+            res: map(),
+            mdl: map(),
+            scr: map(),
+            xsc: map(),
+            gam: map(),
+            lst: map(),
+            dsp: map(),
+            ico: map(),
+            icns: map(),
+            bmp: map(),
+            dat: map(),
+            wav: map(),
+            mp3: map(),
+            other: IndexMap::with_hasher(hash_builder.clone()),
+        };
         match probable_kind {
             ProbableKind::None => {}
             ProbableKind::Tf2Textures => {
@@ -516,8 +2202,9 @@ impl VPKTree {
 
         tree
     }
-
-    pub fn for_ext(&self, ext: &Ext<'_>) -> Option<&DirFileEntryMap> {
+}
+impl<S: BuildHasher> VPKTree<S> {
+    pub fn for_ext(&self, ext: &Ext<'_>) -> Option<&DirFileEntryMap<S>> {
         match ext {
             Ext::Vmt => Some(&self.vmt),
             Ext::Vtf => Some(&self.vtf),
@@ -537,7 +2224,11 @@ impl VPKTree {
             Ext::Dat => Some(&self.dat),
             Ext::Wav => Some(&self.wav),
             Ext::Mp3 => Some(&self.mp3),
-            Ext::Other(ext) => self.other.get(ext.as_ref()),
+            Ext::Other(ext) => match Ext::from_ext_slice(ext) {
+                Ext::Other(ext) => self.other.get(ext.as_ref()),
+                // Constructed directly as `Other` but it has a dedicated map
+                known => self.for_ext(&known),
+            },
         }
     }
 
@@ -548,6 +2239,74 @@ impl VPKTree {
     ) -> Option<&VPKEntry> {
         self.for_ext(ext)?.get(&re)
     }
+}
+impl<S> VPKTree<S> {
+    /// The builder of the hashers of the maps
+    pub fn hasher(&self) -> &S {
+        self.other.hasher()
+    }
+
+    /// Remove every entry, keeping the allocated capacity of the maps for the common extensions.  
+    /// The maps for other extensions are removed, so that extensions that are no longer in the
+    /// tree don't show up as empty maps.
+    pub fn clear(&mut self) {
+        for map in [
+            &mut self.vmt,
+            &mut self.vtf,
+            &mut self.vtx,
+            &mut self.vvd,
+            &mut self.phy,
+            &mut self.res,
+            &mut self.mdl,
+            &mut self.scr,
+            &mut self.xsc,
+            &mut self.gam,
+            &mut self.lst,
+            &mut self.dsp,
+            &mut self.ico,
+            &mut self.icns,
+            &mut self.bmp,
+            &mut self.dat,
+            &mut self.wav,
+            &mut self.mp3,
+        ] {
+            map.clear();
+        }
+        self.other.clear();
+    }
+}
+impl<S: BuildHasher> VPKTree<S> {
+    /// Like [`VPKTree::for_ext`], but also gives the extension borrowed from the tree.
+    pub fn ext_map(&self, ext: &Ext<'_>) -> Option<(Ext<'_>, &DirFileEntryMap<S>)> {
+        if let Ext::Other(other) = ext {
+            if let Ext::Other(other) = Ext::from_ext_slice(other) {
+                let (key, map) = self.other.get_key_value(other.as_ref())?;
+                return Some((Ext::Other(Cow::Borrowed(key.as_slice())), map));
+            }
+        }
+
+        let map = self.for_ext(ext)?;
+        Some((Ext::from_ext_slice(ext.as_slice()).into_owned(), map))
+    }
+
+    /// Get the entry along with its extension and [`DirFile`] key, both borrowed from the tree.
+    pub fn get_entry_direct<K: Equivalent<DirFile> + Hash>(
+        &self,
+        ext: &Ext<'_>,
+        re: K,
+    ) -> Option<(Ext<'_>, &DirFile, &VPKEntry)> {
+        let (ext, map) = self.ext_map(ext)?;
+        let (dir_file, entry) = map.get_key_value(&re)?;
+        Some((ext, dir_file, entry))
+    }
+
+    pub fn get_key_value_direct<K: Equivalent<DirFile> + Hash>(
+        &self,
+        ext: &Ext<'_>,
+        re: K,
+    ) -> Option<(&DirFile, &VPKEntry)> {
+        self.for_ext(ext)?.get_key_value(&re)
+    }
 
     /// Get a path that may be like:  
     /// ext: "vmt"; dir: "materials/" filename: "concrete/concretefloor001a"
@@ -578,7 +2337,69 @@ impl VPKTree {
     pub fn getf_ignore_case(&self, ext: &Ext<'_>, dir: &str, filename: &str) -> Option<&VPKEntry> {
         self.get_direct(ext, DirFileRefLowercase::new(dir, filename))
     }
+}
+impl<S> VPKTree<S> {
+    /// Iterate over every entry in the tree, regardless of extension.
+    pub fn iter(&self) -> Entries<'_, S> {
+        Entries {
+            maps: self.known_maps().map(|(_, map)| map).into_iter(),
+            other: self.other.values(),
+            current: None,
+        }
+    }
+
+    /// Iterate over every entry in the tree along with its extension.  
+    /// Unlike [`VPKTree::iter`], this doesn't require knowing the extension of an entry up front
+    /// to build its path or classify it. Unknown extensions are given as [`Ext::Other`].
+    pub fn iter_with_ext(&self) -> impl Iterator<Item = (Ext<'_>, &DirFile, &VPKEntry)> {
+        self.maps().flat_map(|(ext, map)| {
+            map.iter()
+                .map(move |(dir_file, entry)| (ext.clone(), dir_file, entry))
+        })
+    }
 
+    /// The keys of the `other` map that [`VPKTree::for_ext`] can never look up, because they are
+    /// a known extension with its own map, or aren't lowercase. Entries under them are missed by
+    /// lookups, so this should always be empty for a tree from [`VPK::read`].
+    pub fn misplaced_other_exts(&self) -> impl Iterator<Item = &[u8]> {
+        self.other.keys().map(ExtBytes::as_slice).filter(
+            |key| !matches!(Ext::from_ext_slice(key), Ext::Other(ext) if ext.as_ref() == *key),
+        )
+    }
+
+    /// Iterate over the map for each extension, including the empty ones.
+    pub fn maps(&self) -> impl Iterator<Item = (Ext<'_>, &DirFileEntryMap<S>)> {
+        let other = self
+            .other
+            .iter()
+            .map(|(ext, map)| (Ext::Other(Cow::Borrowed(ext.as_slice())), map));
+        self.known_maps().into_iter().chain(other)
+    }
+
+    fn known_maps(&self) -> [(Ext<'static>, &DirFileEntryMap<S>); 18] {
+        [
+            (Ext::Vmt, &self.vmt),
+            (Ext::Vtf, &self.vtf),
+            (Ext::Vtx, &self.vtx),
+            (Ext::Vvd, &self.vvd),
+            (Ext::Phy, &self.phy),
+            (Ext::Res, &self.res),
+            (Ext::Mdl, &self.mdl),
+            (Ext::Scr, &self.scr),
+            (Ext::Xsc, &self.xsc),
+            (Ext::Gam, &self.gam),
+            (Ext::Lst, &self.lst),
+            (Ext::Dsp, &self.dsp),
+            (Ext::Ico, &self.ico),
+            (Ext::Icns, &self.icns),
+            (Ext::Bmp, &self.bmp),
+            (Ext::Dat, &self.dat),
+            (Ext::Wav, &self.wav),
+            (Ext::Mp3, &self.mp3),
+        ]
+    }
+}
+impl<S: BuildHasher + Clone> VPKTree<S> {
     fn insert(
         &mut self,
         data: Arc<[u8]>,
@@ -586,7 +2407,7 @@ impl VPKTree {
         dir: Range<usize>,
         filename: Range<usize>,
         entry: VPKEntry,
-    ) {
+    ) -> Option<VPKEntry> {
         let re = DirFile::new(data, dir, filename);
 
         match ext {
@@ -610,21 +2431,243 @@ impl VPKTree {
             Ext::Mp3 => self.mp3.insert(re, entry),
             Ext::Other(ext) => {
                 if let Some(map) = self.other.get_mut(ext.as_ref()) {
-                    map.insert(re, entry);
+                    map.insert(re, entry)
                 } else {
-                    let mut map = DirFileEntryMap::default();
+                    let mut map = DirFileEntryMap::with_hasher(self.other.hasher().clone());
                     map.insert(re, entry);
-                    self.other.insert(ext.as_ref().to_vec(), map);
+                    self.other.insert(ExtBytes::from(ext.as_ref()), map);
+                    None
                 }
+            }
+        }
+    }
+}
+
+/// Iterator over all the entries in a [`VPKTree`].  
+/// See [`VPKTree::iter`] and [`VPK::entries`].
+pub struct Entries<'a, S = MapRandomState> {
+    maps: std::array::IntoIter<&'a DirFileEntryMap<S>, 18>,
+    other: indexmap::map::Values<'a, ExtBytes, DirFileEntryMap<S>>,
+    current: Option<indexmap::map::Iter<'a, DirFile, VPKEntry>>,
+}
+// Derived `Clone` would require `S: Clone`, which isn't needed for cloning references
+impl<S> Clone for Entries<'_, S> {
+    fn clone(&self) -> Self {
+        Entries {
+            maps: self.maps.clone(),
+            other: self.other.clone(),
+            current: self.current.clone(),
+        }
+    }
+}
+impl<'a, S> Entries<'a, S> {
+    /// Only keep entries whose [`VPKEntry::total_size`] is within `min..=max`.
+    pub fn filter_by_size(
+        self,
+        min: u64,
+        max: u64,
+    ) -> impl Iterator<Item = (&'a DirFile, &'a VPKEntry)> {
+        self.filter(move |(_, entry)| (min..=max).contains(&entry.total_size()))
+    }
+}
+impl<'a, S> Iterator for Entries<'a, S> {
+    type Item = (&'a DirFile, &'a VPKEntry);
 
-                // for some reason match requires the same return type despite being used as a
-                // statement...
-                None
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.current.as_mut().and_then(Iterator::next) {
+                return Some(entry);
             }
+
+            let map = self.maps.next().or_else(|| self.other.next())?;
+            self.current = Some(map.iter());
+        }
+    }
+}
+
+fn contains_bytes(haystack: &[u8], needle: &[u8], case_insensitive: bool) -> bool {
+    if needle.is_empty() {
+        return true;
+    }
+
+    if case_insensitive {
+        haystack
+            .windows(needle.len())
+            .any(|window| window.eq_ignore_ascii_case(needle))
+    } else {
+        memchr::memmem::find(haystack, needle).is_some()
+    }
+}
+
+/// Whether `dir` is `prefix` or one of its subdirectories, ignoring case and separator kind
+fn dir_has_prefix(dir: &[u8], prefix: &[u8]) -> bool {
+    if prefix.is_empty() {
+        return true;
+    }
+
+    match dir.get(..prefix.len()) {
+        Some(start) if access::path_eq_ignore_case(start, prefix) => {
+            let rest = &dir[prefix.len()..];
+            rest.is_empty() || rest.starts_with(b"/") || rest.starts_with(b"\\")
+        }
+        _ => false,
+    }
+}
+
+/// Get a printable path for an entry while parsing
+fn entry_path_lossy(
+    data: &[u8],
+    ext: &Ext<'_>,
+    dir: Range<usize>,
+    filename: Range<usize>,
+) -> String {
+    format!(
+        "{}/{}.{}",
+        String::from_utf8_lossy(&data[dir]),
+        String::from_utf8_lossy(&data[filename]),
+        String::from_utf8_lossy(ext.as_slice())
+    )
+}
+
+/// If the tree is compressed, replace it with the decompressed tree, giving data that can be
+/// parsed like any other VPK. The tree length in the header is changed to match.  
+/// Invalid headers are left for the parser to report.
+fn decompress_tree(
+    file: Arc<[u8]>,
+    endian: Endian,
+    compression: TreeCompression,
+    max_tree_size: u32,
+) -> Result<Arc<[u8]>, Error> {
+    if compression == TreeCompression::None {
+        return Ok(file);
+    }
+
+    let Ok(header) = VPKHeader::read(&mut file.as_ref(), endian) else {
+        return Ok(file);
+    };
+    if header.signature != VPK_SIGNATURE {
+        return Ok(file);
+    }
+    let header_length = match header.version {
+        1 => mem::size_of::<VPKHeader>(),
+        2 => mem::size_of::<VPKHeader>() + mem::size_of::<VPKHeaderV2>(),
+        _ => return Ok(file),
+    };
+    let tree_end = header_length.saturating_add(header.tree_length as usize);
+    let Some(tree) = file.get(header_length..tree_end) else {
+        return Ok(file);
+    };
+
+    let lzma_header = LzmaHeader::read(tree).filter(|lzma_header| {
+        LzmaHeader::SIZE as u64 + u64::from(lzma_header.lzma_size) <= tree.len() as u64
+    });
+    let Some(lzma_header) = lzma_header else {
+        return match compression {
+            TreeCompression::Lzma => Err(Error::MissingLzmaHeader),
+            _ => Ok(file),
+        };
+    };
+
+    if lzma_header.actual_size > max_tree_size {
+        return Err(Error::TreeTooLarge {
+            size: lzma_header.actual_size,
+            max: max_tree_size,
+        });
+    }
+
+    #[cfg(feature = "lzma")]
+    {
+        let compressed = &tree[LzmaHeader::SIZE..LzmaHeader::SIZE + lzma_header.lzma_size as usize];
+        // Valve's header has the properties but not the size of the standard header, so the
+        // size is given separately
+        let mut input = std::io::Read::chain(&lzma_header.properties[..], compressed);
+        let lzma_options = lzma_rs::decompress::Options {
+            unpacked_size: lzma_rs::decompress::UnpackedSize::UseProvided(Some(u64::from(
+                lzma_header.actual_size,
+            ))),
+            // The dictionary doesn't need to be bigger than the output
+            memlimit: Some(lzma_header.actual_size as usize),
+            ..Default::default()
+        };
+        let mut decompressed = Vec::new();
+        lzma_rs::lzma_decompress_with_options(&mut input, &mut decompressed, &lzma_options)?;
+
+        let header = VPKHeader {
+            tree_length: lzma_header.actual_size,
+            ..header
         };
+        let mut data = Vec::with_capacity(file.len() - tree.len() + decompressed.len());
+        header.write(&mut data, endian)?;
+        data.extend_from_slice(&file[mem::size_of::<VPKHeader>()..header_length]);
+        data.extend_from_slice(&decompressed);
+        data.extend_from_slice(&file[tree_end..]);
+
+        Ok(Arc::from(data))
+    }
+
+    #[cfg(not(feature = "lzma"))]
+    {
+        let _ = lzma_header;
+        Err(Error::CompressedTree)
+    }
+}
+
+/// Big endian VPKs have the signature byte-swapped
+fn detect_endian(data: &[u8]) -> Endian {
+    match data.get(..4) {
+        Some(&[a, b, c, d]) if u32::from_be_bytes([a, b, c, d]) == VPK_SIGNATURE => Endian::Big,
+        _ => Endian::Little,
+    }
+}
+
+/// The checksums are stored as the little-endian `u128` of the MD5 digest
+fn md5_u128(data: &[u8]) -> u128 {
+    u128::from_le_bytes(md5::compute(data).0)
+}
+
+/// Get the path of the archive file with the given index, from the path of the dir file.  
+/// `pak01_dir.vpk` -> `pak01_003.vpk`
+fn archive_path_for(dir_path: &str, archive_dir: Option<&str>, archive_index: u16) -> String {
+    // Only the file name is touched, since a parent directory could also contain `dir.`
+    let name_start = dir_path.rfind(std::path::is_separator).map_or(0, |i| i + 1);
+    let (parent, file_name) = dir_path.split_at(name_start);
+    let file_name = match file_name.rfind("dir.") {
+        Some(i) => format!(
+            "{}{:03}.{}",
+            &file_name[..i],
+            archive_index,
+            &file_name[i + "dir.".len()..]
+        ),
+        None => file_name.to_string(),
+    };
+
+    match archive_dir {
+        // Both parts are already UTF-8, so nothing is lost
+        Some(archive_dir) => Path::new(archive_dir)
+            .join(file_name)
+            .to_string_lossy()
+            .into_owned(),
+        None => format!("{parent}{file_name}"),
     }
 }
 
+/// If the path is to a numbered archive like `pak01_003.vpk`, get the path of its dir file
+fn numbered_archive_dir_path(path: &str) -> Option<String> {
+    let stem = path.strip_suffix(".vpk")?;
+    let split = stem.len().checked_sub(4)?;
+    // Otherwise `split_at` would panic, and the index wouldn't be ascii anyway
+    if !stem.is_char_boundary(split) {
+        return None;
+    }
+    let (start, index) = stem.split_at(split);
+    let index = index.strip_prefix('_')?;
+    if !index.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    Some(format!("{start}_dir.vpk"))
+}
+
 fn read_cstring<'a>(reader: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
     let res = skip_cstring(reader)?;
     Ok(&reader.get_ref()[res])
@@ -636,45 +2679,2307 @@ fn read_cstring<'a>(reader: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
 pub fn skip_cstring(reader: &mut Cursor<&[u8]>) -> Result<Range<usize>, Error> {
     let start = reader.position() as usize;
     let data = reader.get_ref();
-    let v = memchr::memchr(0, &data[start..]).ok_or_else(|| {
-        Error::ReadError(std::io::Error::new(
-            std::io::ErrorKind::UnexpectedEof,
-            "Could not find null byte",
-        ))
-    })?;
+    // The position can be past the end if a length in the tree was bogus
+    let v = data
+        .get(start..)
+        .and_then(|rest| memchr::memchr(0, rest))
+        .ok_or_else(|| {
+            Error::ReadError(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Could not find null byte",
+            ))
+        })?;
 
-    reader.seek(SeekFrom::Start((start + v + 1) as u64))?;
+    reader.set_position((start + v + 1) as u64);
 
     Ok(start..start + v)
 }
 
+/// Trim trailing whitespace and control bytes from the range, see [`ReadOptions::trim_paths`].  
+/// This never makes it empty, since an empty dir or filename ends the list in the tree.
+fn trim_path_end(data: &[u8], mut range: Range<usize>) -> Range<usize> {
+    while range.len() > 1 {
+        let last = data[range.end - 1];
+        if !last.is_ascii_whitespace() && !last.is_ascii_control() {
+            break;
+        }
+        range.end -= 1;
+    }
+
+    range
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+    use std::borrow::Cow;
+    use std::collections::HashMap;
     use std::io::Cursor;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
 
     use crate::{
-        vpk::{read_cstring, ProbableKind},
-        VPK,
+        access::{DirFile, SipRandomState},
+        entry::{VPKDirectoryEntry, VpkReaderProvider},
+        structs::{Endian, LzmaHeader, VPKHeader, VPKHeaderV2Checksum},
+        vpk::{
+            archive_path_for, numbered_archive_dir_path, read_cstring, ArchivePathCache, Ext,
+            ExtBytes, ExtStats, OnDuplicate, OverlapKind, ProbableKind, ReadOptions,
+            TreeCompression, VPK_SIGNATURE,
+        },
+        Error, VPK,
     };
 
-    #[test]
-    fn test_read_cstring_with_null_byte() {
-        let data = b"hello\0world";
-        let mut cursor = Cursor::new(data.as_ref());
+    pub(crate) struct TestEntry {
+        pub ext: &'static str,
+        pub dir: &'static str,
+        pub name: &'static str,
+        pub crc32: u32,
+        pub preload: &'static [u8],
+        pub archive_index: u16,
+        pub archive_offset: u32,
+        pub file_length: u32,
+    }
+    impl TestEntry {
+        /// An entry whose data is entirely stored in the preload section
+        pub fn preload(
+            ext: &'static str,
+            dir: &'static str,
+            name: &'static str,
+            preload: &'static [u8],
+        ) -> TestEntry {
+            TestEntry {
+                ext,
+                dir,
+                name,
+                crc32: 0,
+                preload,
+                archive_index: 0x7fff,
+                archive_offset: 0,
+                file_length: 0,
+            }
+        }
 
-        let result = read_cstring(&mut cursor).unwrap();
-        let remaining_data = &data[cursor.position() as usize..];
+        /// An entry whose data is stored in an archive file
+        pub fn archive(
+            ext: &'static str,
+            dir: &'static str,
+            name: &'static str,
+            archive_index: u16,
+            archive_offset: u32,
+            file_length: u32,
+        ) -> TestEntry {
+            TestEntry {
+                ext,
+                dir,
+                name,
+                crc32: 0,
+                preload: b"",
+                archive_index,
+                archive_offset,
+                file_length,
+            }
+        }
+    }
 
-        assert_eq!(result, b"hello");
-        assert_eq!(remaining_data, b"world");
+    /// Build the tree section of a VPK. Entries with the same ext/dir are grouped together in the
+    /// order they first appear.
+    pub(crate) fn build_tree(entries: &[TestEntry]) -> Vec<u8> {
+        build_tree_endian(entries, Endian::Little)
     }
 
-    #[test]
-    fn test_read_cstring_without_null_byte() {
-        let data = b"hello world"; // No null byte
-        let mut cursor = Cursor::new(data.as_ref());
+    pub(crate) fn build_tree_endian(entries: &[TestEntry], endian: Endian) -> Vec<u8> {
+        let mut exts: Vec<&str> = Vec::new();
+        for entry in entries {
+            if !exts.contains(&entry.ext) {
+                exts.push(entry.ext);
+            }
+        }
 
-        assert!(read_cstring(&mut cursor).is_err());
+        let mut tree = Vec::new();
+        for ext in exts {
+            tree.extend_from_slice(ext.as_bytes());
+            tree.push(0);
+
+            let mut dirs: Vec<&str> = Vec::new();
+            for entry in entries.iter().filter(|e| e.ext == ext) {
+                if !dirs.contains(&entry.dir) {
+                    dirs.push(entry.dir);
+                }
+            }
+
+            for dir in dirs {
+                tree.extend_from_slice(dir.as_bytes());
+                tree.push(0);
+
+                for entry in entries.iter().filter(|e| e.ext == ext && e.dir == dir) {
+                    tree.extend_from_slice(entry.name.as_bytes());
+                    tree.push(0);
+                    let dir_entry = VPKDirectoryEntry {
+                        crc32: entry.crc32,
+                        preload_length: entry.preload.len() as u16,
+                        archive_index: entry.archive_index,
+                        archive_offset: entry.archive_offset,
+                        file_length: entry.file_length,
+                        suffix: 0xffff,
+                    };
+                    dir_entry.write(&mut tree, endian).unwrap();
+                    tree.extend_from_slice(entry.preload);
+                }
+                tree.push(0);
+            }
+            tree.push(0);
+        }
+        tree.push(0);
+
+        tree
+    }
+
+    /// Build a version 1 VPK, with `data` placed directly after the tree.
+    pub(crate) fn build_vpk_v1(entries: &[TestEntry], data: &[u8]) -> Vec<u8> {
+        let tree = build_tree(entries);
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&VPK_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes());
+        out.extend_from_slice(&(tree.len() as u32).to_le_bytes());
+        out.extend_from_slice(&tree);
+        out.extend_from_slice(data);
+
+        out
+    }
+
+    /// Build a version 2 VPK, with `embed` as the embedded chunk and valid checksums.
+    pub(crate) fn build_vpk_v2(entries: &[TestEntry], embed: &[u8]) -> Vec<u8> {
+        let tree = build_tree(entries);
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&VPK_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&2u32.to_le_bytes());
+        out.extend_from_slice(&(tree.len() as u32).to_le_bytes());
+        // embed_chunk_length, chunk_hashes_length, self_hashes_length, signature_length
+        out.extend_from_slice(&(embed.len() as u32).to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&48u32.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&tree);
+        out.extend_from_slice(embed);
+        // No chunk hashes
+        out.extend_from_slice(&md5::compute(&tree).0);
+        out.extend_from_slice(&md5::compute(b"").0);
+        let file_checksum = md5::compute(&out).0;
+        out.extend_from_slice(&file_checksum);
+
+        out
+    }
+
+    /// Write `data` to a temporary directory unique to the test, returning the file path.  
+    /// The directory is removed when the returned file is dropped.
+    pub(crate) fn write_temp(test_name: &str, file_name: &str, data: &[u8]) -> TempFile {
+        let dir = temp_dir(test_name);
+        let path = dir.join(file_name);
+        std::fs::write(&path, data).unwrap();
+
+        TempFile { path, _dir: dir }
+    }
+
+    /// Write a version 1 dir file of `entries` to a temporary directory unique to the test, along
+    /// with `archives` as the numbered archive files next to it, and read it.  
+    /// The dir file is returned so that the directory is kept around, see [`write_temp`].
+    pub(crate) fn read_with_archives(
+        test_name: &str,
+        entries: &[TestEntry],
+        archives: &[&[u8]],
+    ) -> (VPK, TempFile) {
+        let path = write_temp(test_name, "pak01_dir.vpk", &build_vpk_v1(entries, b""));
+        for (i, archive) in archives.iter().enumerate() {
+            std::fs::write(path.with_file_name(format!("pak01_{i:03}.vpk")), archive).unwrap();
+        }
+
+        (VPK::read(&path, ProbableKind::None).unwrap(), path)
+    }
+
+    /// Get a temporary directory unique to the test, which is removed along with everything in
+    /// it when dropped.
+    pub(crate) fn temp_dir(test_name: &str) -> TempDir {
+        let dir =
+            std::env::temp_dir().join(format!("vpk-rs-test-{}-{}", std::process::id(), test_name));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        TempDir(dir)
+    }
+
+    /// See [`temp_dir`]
+    #[derive(Debug)]
+    pub(crate) struct TempDir(PathBuf);
+    impl std::ops::Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+    impl AsRef<Path> for TempDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            // Another file of the same test may have removed it already
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// See [`write_temp`]
+    #[derive(Debug)]
+    pub(crate) struct TempFile {
+        path: PathBuf,
+        _dir: TempDir,
+    }
+    impl std::ops::Deref for TempFile {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.path
+        }
+    }
+    impl AsRef<Path> for TempFile {
+        fn as_ref(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    #[test]
+    fn test_read_cstring_with_null_byte() {
+        let data = b"hello\0world";
+        let mut cursor = Cursor::new(data.as_ref());
+
+        let result = read_cstring(&mut cursor).unwrap();
+        let remaining_data = &data[cursor.position() as usize..];
+
+        assert_eq!(result, b"hello");
+        assert_eq!(remaining_data, b"world");
+    }
+
+    #[test]
+    fn test_read_cstring_without_null_byte() {
+        let data = b"hello world"; // No null byte
+        let mut cursor = Cursor::new(data.as_ref());
+
+        assert!(read_cstring(&mut cursor).is_err());
+    }
+
+    #[test]
+    fn test_read_archive_dir() {
+        let entries = [TestEntry::archive("vtf", "materials", "brick", 0, 2, 3)];
+        let dir_path = write_temp("archive_dir", "pak01_dir.vpk", &build_vpk_v1(&entries, b""));
+
+        let archive_dir = temp_dir("archive_dir_archives");
+        std::fs::write(archive_dir.join("pak01_000.vpk"), b"..abc..").unwrap();
+
+        let options = ReadOptions {
+            archive_dir: Some(archive_dir.to_path_buf()),
+            ..ReadOptions::default()
+        };
+        let vpk = VPK::read_with_options(&dir_path, &options).unwrap();
+        assert_eq!(
+            vpk.archive_path(0),
+            archive_dir.join("pak01_000.vpk").to_str()
+        );
+
+        let entry = vpk.get(&Ext::Vtf, "materials", "brick").unwrap();
+        assert_eq!(entry.get().unwrap().as_ref(), b"abc");
+
+        // Without the override the archives are expected next to the dir file
+        let vpk = VPK::read(&dir_path, ProbableKind::None).unwrap();
+        assert_eq!(
+            vpk.archive_path(0),
+            dir_path.with_file_name("pak01_000.vpk").to_str()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_non_utf8_archive_dir() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let entries = [TestEntry::archive("vtf", "materials", "brick", 0, 0, 3)];
+        let dir_path = write_temp(
+            "non_utf8_archive_dir",
+            "pak01_dir.vpk",
+            &build_vpk_v1(&entries, b""),
+        );
+
+        let archive_dir = PathBuf::from(std::ffi::OsStr::from_bytes(b"archives\xff"));
+        let options = ReadOptions {
+            archive_dir: Some(archive_dir.clone()),
+            ..ReadOptions::default()
+        };
+        assert!(matches!(
+            VPK::read_with_options(&dir_path, &options),
+            Err(Error::NonUtf8ArchiveDir(dir)) if dir == archive_dir
+        ));
+    }
+
+    #[test]
+    fn test_entries_by_size() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "small", b"a"),
+            TestEntry::archive("vtf", "materials", "big", 0, 0, 1000),
+            TestEntry::archive("xyz", "other", "medium", 0, 1000, 100),
+            TestEntry::preload("vmt", "materials", "empty", b""),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        assert_eq!(vpk.entries().count(), 4);
+        let total: usize = vpk
+            .iter()
+            .filter(|handle| handle.archive_index() == 0x7fff)
+            .map(|handle| handle.get().unwrap().len())
+            .sum();
+        assert_eq!(total, 1);
+        let (dir_file, _) = vpk.entry_at(&Ext::Vmt, 1).unwrap();
+        assert_eq!(dir_file.filename(), b"empty");
+        assert!(vpk.entry_at(&Ext::Vmt, 2).is_none());
+        assert!(vpk.entry_at(&Ext::Mdl, 0).is_none());
+
+        let mut names: Vec<_> = vpk
+            .entries()
+            .filter_by_size(1, 100)
+            .map(|(dir_file, _)| dir_file.filename())
+            .collect();
+        names.sort();
+        assert_eq!(names, [b"medium".as_slice(), b"small"]);
+
+        let largest: Vec<_> = vpk
+            .largest_entries(2)
+            .into_iter()
+            .map(|(dir_file, entry)| (dir_file.filename(), entry.total_size()))
+            .collect();
+        assert_eq!(largest, [(b"big".as_slice(), 1000), (b"medium", 100)]);
+    }
+
+    #[test]
+    fn test_verify_checksums() {
+        let entries = [TestEntry::preload("vmt", "materials", "brick", b"abc")];
+        let mut data = build_vpk_v2(&entries, b"");
+        let vpk = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None).unwrap();
+        assert!(vpk.verify_tree_checksum().unwrap());
+        assert!(vpk.verify_chunk_hashes_checksum().unwrap());
+        assert!(vpk.verify_file_checksum().unwrap());
+        assert!(vpk.verify_checksums().unwrap());
+
+        // Modify the preload data, which only the tree and file checksum cover
+        let preload_pos = data.windows(3).position(|w| w == b"abc").unwrap();
+        data[preload_pos] = b'x';
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert!(!vpk.verify_tree_checksum().unwrap());
+        assert!(vpk.verify_chunk_hashes_checksum().unwrap());
+        assert!(!vpk.verify_file_checksum().unwrap());
+        assert!(!vpk.verify_checksums().unwrap());
+
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert!(matches!(vpk.verify_checksums(), Err(Error::NoChecksums)));
+    }
+
+    #[test]
+    fn test_entries_in_file_order() {
+        let entries = [
+            TestEntry::preload("vtf", "Materials/Brick", "WallA", b""),
+            TestEntry::preload("vtf", "Materials/Brick", "wallb", b""),
+            TestEntry::preload("vmt", "Materials/Brick", "WallA", b""),
+            TestEntry::preload("VMT", "MATERIALS", "Floor", b""),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let names: Vec<_> = vpk
+            .entries_in_file_order()
+            .into_iter()
+            .map(|(ext, dir_file, _)| (ext, dir_file.dir(), dir_file.filename()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (Ext::Vtf, b"Materials/Brick".as_slice(), b"WallA".as_slice()),
+                (Ext::Vtf, b"Materials/Brick", b"wallb"),
+                (Ext::Vmt, b"Materials/Brick", b"WallA"),
+                (Ext::Vmt, b"MATERIALS", b"Floor"),
+            ]
+        );
+    }
+
+    /// Always has the entry open the archive file itself
+    pub(crate) struct OpenArchives;
+    impl VpkReaderProvider for OpenArchives {
+        type Reader<'a> = std::fs::File;
+
+        fn vpk_reader(&self, _archive_index: u16) -> std::io::Result<Option<std::fs::File>> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn test_read_empty_v1() {
+        let data = build_vpk_v1(&[], b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        assert_eq!(vpk.header.tree_length, 1);
+        assert_eq!(vpk.entries().count(), 0);
+        assert!(vpk.archive_paths.is_empty());
+    }
+
+    #[test]
+    fn test_read_empty_v2() {
+        let data = build_vpk_v2(&[], b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        assert_eq!(vpk.header_v2.unwrap().embed_chunk_length, 0);
+        assert_eq!(vpk.entries().count(), 0);
+        assert!(vpk.archive_paths.is_empty());
+        assert!(vpk.verify_checksums().unwrap());
+    }
+
+    #[test]
+    fn test_read_zero_length_entries() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "empty", b""),
+            TestEntry::archive("vtf", "materials", "empty", 0, 0, 0),
+        ];
+        // The archive file intentionally doesn't exist
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert_eq!(vpk.archive_paths.len(), 1);
+
+        let preload = vpk.get(&Ext::Vmt, "materials", "empty").unwrap();
+        assert_eq!(preload.get().unwrap().as_ref(), b"");
+
+        let archive = vpk.get(&Ext::Vtf, "materials", "empty").unwrap();
+        assert_eq!(archive.get().unwrap().as_ref(), b"");
+    }
+
+    #[test]
+    fn test_summary() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::preload("vmt", "materials", "b", b"de"),
+            TestEntry::archive("xyz", "other", "c", 0, 0, 10),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let summary = vpk.summary();
+        assert_eq!(summary.entry_count, 3);
+        assert_eq!(summary.total_size, 15);
+        assert_eq!(
+            summary.to_string(),
+            "VPK v1: 3 entries, 1 archives, 15 bytes\n  vmt: 2\n  xyz: 1"
+        );
+    }
+
+    #[test]
+    fn test_validate_archives() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "a", 0, 0, 1),
+            TestEntry::archive("vtf", "materials", "b", 2, 0, 1),
+        ];
+        let path = write_temp(
+            "validate_archives",
+            "pak01_dir.vpk",
+            &build_vpk_v1(&entries, b""),
+        );
+        std::fs::write(path.with_file_name("pak01_000.vpk"), b"a").unwrap();
+
+        // Not validated by default
+        VPK::read(&path, ProbableKind::None).unwrap();
+
+        let options = ReadOptions {
+            validate_archives: true,
+            ..ReadOptions::default()
+        };
+        match VPK::read_with_options(&path, &options) {
+            Err(Error::MissingArchives(missing)) => {
+                assert_eq!(
+                    missing,
+                    [path.with_file_name("pak01_002.vpk").to_str().unwrap()]
+                );
+            }
+            res => panic!("expected missing archives, got {:?}", res),
+        }
+
+        std::fs::write(path.with_file_name("pak01_002.vpk"), b"b").unwrap();
+        VPK::read_with_options(&path, &options).unwrap();
+    }
+
+    #[test]
+    fn test_read_big_endian() {
+        let entries = [TestEntry {
+            crc32: 0x01020304,
+            ..TestEntry::archive("vtf", "materials", "brick", 1, 0x1000, 0x20)
+        }];
+        let tree = build_tree_endian(&entries, Endian::Big);
+        let mut data = Vec::new();
+        data.extend_from_slice(&VPK_SIGNATURE.to_be_bytes());
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(&(tree.len() as u32).to_be_bytes());
+        data.extend_from_slice(&tree);
+
+        let vpk = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None).unwrap();
+        assert_eq!(vpk.endian, Endian::Big);
+        assert_eq!(vpk.header.tree_length, tree.len() as u32);
+        let entry = vpk.get(&Ext::Vtf, "materials", "brick").unwrap();
+        assert_eq!(entry.crc32(), 0x01020304);
+        assert_eq!(entry.archive_index(), 1);
+        assert_eq!(entry.entry.dir_entry.archive_offset, 0x1000);
+        assert_eq!(entry.entry.dir_entry.file_length, 0x20);
+
+        // Forcing the wrong endianness fails on the signature
+        let options = ReadOptions {
+            endian: Some(Endian::Little),
+            ..ReadOptions::default()
+        };
+        assert!(matches!(
+            VPK::read_bytes_with_options(data, |i| i.to_string(), &options),
+            Err(Error::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_intern_dirs() {
+        let entries = [
+            TestEntry::preload("vmt", "materials/brick", "a", b""),
+            TestEntry::preload("vmt", "materials/brick", "b", b""),
+            TestEntry::preload("vtf", "materials/brick", "a", b""),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let dir_ranges = |vpk: &VPK| {
+            let vmt_a = vpk.tree.vmt.get_index(0).unwrap().0.dir_range();
+            let vmt_b = vpk.tree.vmt.get_index(1).unwrap().0.dir_range();
+            let vtf_a = vpk.tree.vtf.get_index(0).unwrap().0.dir_range();
+            (vmt_a, vmt_b, vtf_a)
+        };
+
+        // Already shared within an extension
+        let vpk = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None).unwrap();
+        let (vmt_a, vmt_b, vtf_a) = dir_ranges(&vpk);
+        assert_eq!(vmt_a, vmt_b);
+        assert_ne!(vmt_a, vtf_a);
+
+        let options = ReadOptions {
+            intern_dirs: true,
+            ..ReadOptions::default()
+        };
+        let vpk = VPK::read_bytes_with_options(data, |i| i.to_string(), &options).unwrap();
+        let (vmt_a, vmt_b, vtf_a) = dir_ranges(&vpk);
+        assert_eq!(vmt_a, vmt_b);
+        assert_eq!(vmt_a, vtf_a);
+    }
+
+    #[test]
+    fn test_read_lenient() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::preload("vmt", "materials", "b", b"def"),
+            TestEntry::preload("vtf", "materials", "c", b"ghi"),
+        ];
+        let mut data = build_vpk_v1(&entries, b"");
+        // Break the suffix of `b`, which comes right before its preload data
+        let b_preload = data.windows(3).position(|w| w == b"def").unwrap();
+        data[b_preload - 1] = 0;
+
+        // The position of `b`'s dir entry, which comes right before its preload data
+        assert!(matches!(
+            VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None),
+            Err(Error::MalformedIndex { position, .. }) if position == b_preload - 18
+        ));
+
+        let options = ReadOptions {
+            lenient: true,
+            ..ReadOptions::default()
+        };
+        let vpk = VPK::read_bytes_with_options(data.clone(), |i| i.to_string(), &options).unwrap();
+        assert!(vpk.get(&Ext::Vmt, "materials", "a").is_some());
+        assert!(vpk.get(&Ext::Vmt, "materials", "b").is_none());
+        assert!(vpk.get(&Ext::Vtf, "materials", "c").is_some());
+        assert_eq!(vpk.skipped_entries.len(), 1);
+        assert_eq!(vpk.skipped_entries[0].path, "materials/b.vmt");
+        assert!(matches!(
+            *vpk.skipped_entries[0].error,
+            Error::MalformedIndex { .. }
+        ));
+
+        // Cut off in the middle of the tree
+        let c_name = data.windows(2).position(|w| w == b"c\0").unwrap();
+        let data = data[..c_name + 4].to_vec();
+        // Without the broken suffix of `b`, it stops at the dir entry of `c`, which is cut off
+        let clean = build_vpk_v1(&entries, b"");
+        match VPK::read_bytes(&clean[..c_name + 4], |i| i.to_string(), ProbableKind::None)
+            .unwrap_err()
+        {
+            Error::TreeReadError { position, error } => {
+                assert!(position >= c_name + 2);
+                assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+            }
+            err => panic!("unexpected error: {err}"),
+        }
+        let vpk = VPK::read_bytes_with_options(data, |i| i.to_string(), &options).unwrap();
+        assert!(vpk.get(&Ext::Vmt, "materials", "a").is_some());
+        assert!(vpk.get(&Ext::Vtf, "materials", "c").is_none());
+        assert_eq!(vpk.skipped_entries.len(), 2);
+        assert_eq!(vpk.skipped_entries[1].path, "");
+        assert_eq!(vpk.malformed_entry_count(), 1);
+    }
+
+    #[test]
+    fn test_read_lenient_max_skipped() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::preload("vmt", "materials", "b", b"def"),
+            TestEntry::preload("vmt", "materials", "c", b"ghi"),
+            TestEntry::preload("vtf", "materials", "d", b"jkl"),
+        ];
+        let mut data = build_vpk_v1(&entries, b"");
+        // Break the suffixes of the first three entries
+        for preload in [b"abc", b"def", b"ghi"] {
+            let preload = data.windows(3).position(|w| w == preload).unwrap();
+            data[preload - 2] = 0x7f;
+        }
+
+        let options = |max_entries| ReadOptions {
+            lenient: true,
+            max_entries,
+            ..ReadOptions::default()
+        };
+        let vpk =
+            VPK::read_bytes_with_options(data.clone(), |i| i.to_string(), &options(None)).unwrap();
+        assert_eq!(vpk.skipped_entries.len(), 3);
+        assert!(vpk.get(&Ext::Vtf, "materials", "d").is_some());
+
+        // The skipped entries are bounded too, so reading stops once there are too many
+        let vpk = VPK::read_bytes_with_options(data, |i| i.to_string(), &options(Some(2))).unwrap();
+        assert_eq!(vpk.malformed_entry_count(), 2);
+        assert_eq!(vpk.skipped_entries.len(), 3);
+        assert!(matches!(
+            *vpk.skipped_entries[2].error,
+            Error::TooManyEntries { max: 2 }
+        ));
+        assert!(vpk.get(&Ext::Vtf, "materials", "d").is_none());
+    }
+
+    #[test]
+    fn test_malformed_entry_count() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::preload("vmt", "materials", "b", b"def"),
+            TestEntry::preload("vtf", "materials", "c", b"ghi"),
+        ];
+        let mut data = build_vpk_v1(&entries, b"");
+        // Break the suffixes of `a` and `c`, like a damaged file would
+        for preload in [b"abc", b"ghi"] {
+            let preload = data.windows(3).position(|w| w == preload).unwrap();
+            data[preload - 2] = 0x7f;
+        }
+
+        let options = ReadOptions {
+            lenient: true,
+            ..ReadOptions::default()
+        };
+        let vpk = VPK::read_bytes_with_options(data.clone(), |i| i.to_string(), &options).unwrap();
+        assert_eq!(vpk.malformed_entry_count(), 2);
+        assert!(vpk.get(&Ext::Vmt, "materials", "b").is_some());
+
+        let positions: Vec<_> = vpk.skipped_entries.iter().map(|s| s.position).collect();
+        let name_position = |name: &[u8]| data.windows(2).position(|w| w == name).unwrap();
+        assert_eq!(positions, [name_position(b"a\0"), name_position(b"c\0")]);
+    }
+
+    #[test]
+    fn test_ext_ord() {
+        let mut exts = vec![
+            Ext::from_ext_slice(b"txt"),
+            Ext::Vtf,
+            Ext::from_ext_slice(b"cfg"),
+            Ext::Mp3,
+            Ext::Vmt,
+        ];
+        exts.sort();
+        assert_eq!(
+            exts,
+            [
+                Ext::Vmt,
+                Ext::Vtf,
+                Ext::Mp3,
+                Ext::from_ext_slice(b"cfg"),
+                Ext::from_ext_slice(b"txt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_bytes() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::archive("vtf", "materials", "b", 1, 0, 3),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk =
+            VPK::read_bytes(data, |i| format!("archives/{}.vpk", i), ProbableKind::None).unwrap();
+
+        let a = vpk.get(&Ext::Vmt, "materials", "a").unwrap();
+        assert_eq!(a.get().unwrap().as_ref(), b"abc");
+        assert_eq!(vpk.archive_path(1), Some("archives/1.vpk"));
+        assert!(vpk.source_modified().is_err());
+        assert_eq!(vpk.dir_path(), None);
+    }
+
+    #[test]
+    fn test_get_entry() {
+        let entries = [TestEntry::preload("vmt", "Materials/Brick", "WallA", b"")];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let (dir_file, handle) = vpk
+            .get_entry_ignore_case(&Ext::Vmt, "materials", "brick/walla")
+            .unwrap();
+        assert_eq!(dir_file.dir(), b"Materials/Brick");
+        assert_eq!(dir_file.filename(), b"WallA");
+        assert_eq!(handle.archive_index(), 0x7fff);
+
+        let (dir_file, _) = vpk
+            .get_entry(&Ext::Vmt, "Materials", "Brick/WallA")
+            .unwrap();
+        assert_eq!(dir_file.filename(), b"WallA");
+    }
+
+    #[test]
+    fn test_is_self_contained() {
+        let read = |entries: &[TestEntry]| {
+            let data = build_vpk_v2(entries, b"xyz");
+            VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap()
+        };
+
+        assert!(read(&[]).is_self_contained());
+        assert!(read(&[
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::archive("vtf", "materials", "b", 0x7fff, 0, 3),
+            TestEntry {
+                preload: b"abc",
+                ..TestEntry::archive("vtf", "materials", "c", 2, 0, 0)
+            },
+        ])
+        .is_self_contained());
+        assert!(!read(&[
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::archive("vtf", "materials", "b", 0, 0, 3),
+        ])
+        .is_self_contained());
+    }
+
+    #[test]
+    fn test_stats() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry {
+                preload: b"de",
+                ..TestEntry::archive("vmt", "materials", "b", 0, 0, 10)
+            },
+            TestEntry::archive("vtf", "materials", "c", 2, 0, 100),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let stats = vpk.stats();
+        let vmt = ExtStats {
+            count: 2,
+            total_bytes: 15,
+            preload_bytes: 5,
+        };
+        let vtf = ExtStats {
+            count: 1,
+            total_bytes: 100,
+            preload_bytes: 0,
+        };
+        assert_eq!(stats.per_ext, [(Ext::Vmt, vmt), (Ext::Vtf, vtf)]);
+        assert_eq!(
+            stats.total,
+            ExtStats {
+                count: 3,
+                total_bytes: 115,
+                preload_bytes: 5,
+            }
+        );
+        // Only archives 0 and 2 have entries
+        assert_eq!(stats.archive_count, 2);
+
+        let summary = vpk.summary();
+        assert_eq!(summary.entry_count, 3);
+        assert_eq!(summary.total_size, 115);
+        assert_eq!(summary.ext_counts, [(Ext::Vmt, 2), (Ext::Vtf, 1)]);
+    }
+
+    #[test]
+    fn test_tree_compression() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::archive("txt", "scripts", "b", 0x7fff, 1, 2),
+        ];
+        let data = build_vpk_v2(&entries, b"xyz");
+        let tree = build_tree(&entries);
+
+        let read = |data: &[u8], tree_compression| {
+            let options = ReadOptions {
+                tree_compression,
+                ..ReadOptions::default()
+            };
+            VPK::read_bytes_with_options(data.to_vec(), |i| i.to_string(), &options)
+        };
+        let expected_default = if cfg!(feature = "lzma") {
+            TreeCompression::Detect
+        } else {
+            TreeCompression::None
+        };
+        assert_eq!(TreeCompression::default(), expected_default);
+        let vpk = read(&data, TreeCompression::Detect).unwrap();
+        assert_eq!(vpk.entries().count(), 2);
+        assert!(matches!(
+            read(&data, TreeCompression::Lzma),
+            Err(Error::MissingLzmaHeader)
+        ));
+
+        #[cfg(feature = "lzma")]
+        let compressed = {
+            let mut standard = Vec::new();
+            lzma_rs::lzma_compress(&mut tree.as_slice(), &mut standard).unwrap();
+            // The standard header is the properties and then the 8 byte size
+            let header = LzmaHeader {
+                actual_size: tree.len() as u32,
+                lzma_size: (standard.len() - 13) as u32,
+                properties: standard[..5].try_into().unwrap(),
+            };
+            let mut compressed = Vec::new();
+            header.write(&mut compressed).unwrap();
+            compressed.extend_from_slice(&standard[13..]);
+            compressed
+        };
+        #[cfg(not(feature = "lzma"))]
+        let compressed = {
+            let header = LzmaHeader {
+                actual_size: tree.len() as u32,
+                lzma_size: 0,
+                properties: [0; 5],
+            };
+            let mut compressed = Vec::new();
+            header.write(&mut compressed).unwrap();
+            compressed
+        };
+        assert_eq!(
+            LzmaHeader::read(&compressed).unwrap().actual_size,
+            tree.len() as u32
+        );
+
+        let mut data_compressed = data[..28].to_vec();
+        data_compressed[8..12].copy_from_slice(&(compressed.len() as u32).to_le_bytes());
+        data_compressed.extend_from_slice(&compressed);
+        data_compressed.extend_from_slice(&data[28 + tree.len()..]);
+
+        // Read as is, the header is the start of an extension string
+        let vpk = read(&data_compressed, TreeCompression::None).unwrap();
+        assert_eq!(vpk.header.tree_length, compressed.len() as u32);
+        assert!(vpk.get(&Ext::Vmt, "materials", "a").is_none());
+
+        // The size is checked before decompressing anything
+        let options = ReadOptions {
+            tree_compression: TreeCompression::Lzma,
+            max_tree_size: tree.len() as u32 - 1,
+            ..ReadOptions::default()
+        };
+        let res =
+            VPK::read_bytes_with_options(data_compressed.clone(), |i| i.to_string(), &options);
+        assert!(matches!(
+            res,
+            Err(Error::TreeTooLarge { size, max }) if size == tree.len() as u32 && max == size - 1
+        ));
+
+        #[cfg(feature = "lzma")]
+        {
+            for tree_compression in [TreeCompression::Detect, TreeCompression::Lzma] {
+                let vpk = read(&data_compressed, tree_compression).unwrap();
+                assert_eq!(vpk.header.tree_length, tree.len() as u32);
+                assert_eq!(vpk.data.as_ref(), data.as_slice());
+
+                let a = vpk.get(&Ext::Vmt, "materials", "a").unwrap();
+                assert_eq!(a.get().unwrap().as_ref(), b"abc");
+                let b = vpk.get_path("scripts/b.txt").unwrap();
+                assert_eq!(b.get().unwrap().as_ref(), b"yz");
+            }
+        }
+        #[cfg(not(feature = "lzma"))]
+        assert!(matches!(
+            read(&data_compressed, TreeCompression::Detect),
+            Err(Error::CompressedTree)
+        ));
+    }
+
+    #[test]
+    fn test_tree_terminators() {
+        let dir_entry = |crc32| {
+            let mut out = Vec::new();
+            VPKDirectoryEntry {
+                crc32,
+                preload_length: 0,
+                archive_index: 0x7fff,
+                archive_offset: 0,
+                file_length: 0,
+                suffix: 0xffff,
+            }
+            .write_le(&mut out)
+            .unwrap();
+            out
+        };
+        let with_header = |tree: &[u8]| {
+            let mut out = Vec::new();
+            VPKHeader {
+                signature: VPK_SIGNATURE,
+                version: 1,
+                tree_length: tree.len() as u32,
+            }
+            .write_le(&mut out)
+            .unwrap();
+            out.extend_from_slice(tree);
+            out
+        };
+
+        // ext, path, name, entry, end of path, name, entry, end of path, end of ext, then the
+        // same for a second ext, and the end of the tree
+        let mut tree = Vec::new();
+        tree.extend_from_slice(b"vmt\0materials\0a\0");
+        tree.extend_from_slice(&dir_entry(1));
+        tree.extend_from_slice(b"\0other\0b\0");
+        tree.extend_from_slice(&dir_entry(2));
+        tree.extend_from_slice(b"\0\0vtf\0materials\0c\0");
+        tree.extend_from_slice(&dir_entry(3));
+        tree.extend_from_slice(b"\0\0");
+        let mut terminated = tree.clone();
+        terminated.push(0);
+
+        for (name, tree) in [("terminated", terminated), ("unterminated", tree)] {
+            let data = with_header(&tree);
+            let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+            assert_eq!(vpk.entries().count(), 3, "{}", name);
+            let crc = |ext, dir, filename| vpk.get(ext, dir, filename).unwrap().crc32();
+            assert_eq!(crc(&Ext::Vmt, "materials", "a"), 1);
+            assert_eq!(crc(&Ext::Vmt, "other", "b"), 2);
+            assert_eq!(crc(&Ext::Vtf, "materials", "c"), 3);
+        }
+
+        // The tree ends inside a dir, without the terminators for the names, paths and exts.
+        // What follows it looks like another entry, but isn't part of the tree.
+        let mut tree = Vec::new();
+        tree.extend_from_slice(b"vmt\0materials\0a\0");
+        tree.extend_from_slice(&dir_entry(1));
+        let mut after_tree = b"junk\0".to_vec();
+        after_tree.extend_from_slice(&dir_entry(9));
+        after_tree.extend_from_slice(b"\0\0\0");
+
+        let mut after_path = tree.clone();
+        after_path.push(0);
+        for (name, tree) in [("in names", tree), ("in paths", after_path)] {
+            let mut data = with_header(&tree);
+            data.extend_from_slice(&after_tree);
+            let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+            assert_eq!(vpk.entries().count(), 1, "{}", name);
+            assert_eq!(vpk.get(&Ext::Vmt, "materials", "a").unwrap().crc32(), 1);
+            assert!(vpk.get(&Ext::Vmt, "materials", "junk").is_none());
+        }
+    }
+
+    #[test]
+    fn test_padded_tree() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::preload("vtf", "materials", "b", b"de"),
+        ];
+        let mut tree = build_tree(&entries);
+        // Pad past the terminator to an alignment boundary, with at least a few extra nulls
+        tree.extend_from_slice(&[0; 3]);
+        while tree.len() % 16 != 0 {
+            tree.push(0);
+        }
+
+        let mut data = Vec::new();
+        VPKHeader {
+            signature: VPK_SIGNATURE,
+            version: 1,
+            tree_length: tree.len() as u32,
+        }
+        .write_le(&mut data)
+        .unwrap();
+        data.extend_from_slice(&tree);
+
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert_eq!(vpk.entries().count(), 2);
+        assert!(vpk.skipped_entries.is_empty());
+        let a = vpk.get(&Ext::Vmt, "materials", "a").unwrap();
+        assert_eq!(a.get().unwrap().as_ref(), b"abc");
+        let b = vpk.get(&Ext::Vtf, "materials", "b").unwrap();
+        assert_eq!(b.get().unwrap().as_ref(), b"de");
+    }
+
+    #[test]
+    fn test_entries_by_dir() {
+        let entries = [
+            TestEntry::preload("mdl", "models/weapons", "gun", b""),
+            TestEntry::preload("vvd", "Models/Weapons", "gun", b""),
+            TestEntry::preload("vtx", "models/weapons/v_models", "gun", b""),
+            TestEntry::preload("mdl", "models/weaponsextra", "gun", b""),
+            TestEntry::preload("mdl", "models/props", "box", b""),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let mut found: Vec<_> = vpk
+            .entries_by_dir("models/weapons/")
+            .map(|(ext, dir_file, _)| (ext, dir_file.dir()))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                (Ext::Vtx, b"models/weapons/v_models".as_slice()),
+                (Ext::Vvd, b"Models/Weapons"),
+                (Ext::Mdl, b"models/weapons"),
+            ]
+        );
+
+        assert_eq!(vpk.entries_by_dir("").count(), 5);
+    }
+
+    #[test]
+    fn test_archive_path_for() {
+        assert_eq!(
+            archive_path_for("./tf/tf2_misc_dir.vpk", None, 3),
+            "./tf/tf2_misc_003.vpk"
+        );
+        assert_eq!(
+            archive_path_for("/games/mydir.stuff/pak01_dir.vpk", None, 12),
+            "/games/mydir.stuff/pak01_012.vpk"
+        );
+        assert_eq!(archive_path_for("pak01_dir.vpk", None, 0), "pak01_000.vpk");
+
+        let entries = [TestEntry::archive("vtf", "materials", "brick", 0, 0, 3)];
+        let parent = temp_dir("archive_path_dir_parent");
+        let dir = parent.join("mydir.stuff");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pak01_dir.vpk");
+        std::fs::write(&path, build_vpk_v1(&entries, b"")).unwrap();
+        std::fs::write(dir.join("pak01_000.vpk"), b"abc").unwrap();
+
+        let vpk = VPK::read(&path, ProbableKind::None).unwrap();
+        assert_eq!(vpk.archive_path(0), dir.join("pak01_000.vpk").to_str());
+        assert_eq!(vpk.dir_path(), Some(path.as_path()));
+        let brick = vpk.get(&Ext::Vtf, "materials", "brick").unwrap();
+        assert_eq!(brick.get().unwrap().as_ref(), b"abc");
+    }
+
+    #[test]
+    fn test_find_by_name() {
+        let entries = [
+            TestEntry::preload("vmt", "materials/effects", "explosion01", b""),
+            TestEntry::preload("vtf", "materials/effects", "Big_Explosion", b""),
+            TestEntry::preload("wav", "sound", "explosion", b""),
+            TestEntry::preload("vmt", "explosion", "smoke", b""),
+            TestEntry::preload("txt", "scripts", "explosion_sounds", b""),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let names = |found: Vec<(Ext, &DirFile, _)>| {
+            let mut names: Vec<_> = found
+                .into_iter()
+                .map(|(_, dir_file, _)| dir_file.filename().to_vec())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let found = vpk.find_by_name("explosion", false, None).collect();
+        assert_eq!(
+            names(found),
+            [
+                b"explosion".to_vec(),
+                b"explosion01".to_vec(),
+                b"explosion_sounds".to_vec()
+            ]
+        );
+
+        let found = vpk.find_by_name("EXPLOSION", true, None).collect();
+        assert_eq!(
+            names(found),
+            [
+                b"Big_Explosion".to_vec(),
+                b"explosion".to_vec(),
+                b"explosion01".to_vec(),
+                b"explosion_sounds".to_vec()
+            ]
+        );
+
+        let found = vpk
+            .find_by_name("explosion", true, Some(&Ext::Vtf))
+            .collect();
+        assert_eq!(names(found), [b"Big_Explosion".to_vec()]);
+
+        // The extension is compared ignoring case, like `Ext` itself
+        for ext in [b"txt".as_slice(), b"TXT"] {
+            let ext = Ext::Other(Cow::Borrowed(ext));
+            let found = vpk.find_by_name("explosion", false, Some(&ext)).collect();
+            assert_eq!(names(found), [b"explosion_sounds".to_vec()]);
+        }
+        let ext = Ext::Other(Cow::Borrowed(b"VTF".as_slice()));
+        let found = vpk.find_by_name("explosion", true, Some(&ext)).collect();
+        assert_eq!(names(found), [b"Big_Explosion".to_vec()]);
+        let ext = Ext::Other(Cow::Borrowed(b"cfg".as_slice()));
+        assert_eq!(vpk.find_by_name("explosion", true, Some(&ext)).count(), 0);
+    }
+
+    #[test]
+    fn test_embed_chunk() {
+        let entries = [TestEntry::preload("vmt", "materials", "brick", b"abc")];
+        let data = build_vpk_v2(&entries, b"embedded");
+        let mut vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert_eq!(vpk.embed_chunk(), Some(b"embedded".as_slice()));
+
+        vpk.header_v2.as_mut().unwrap().embed_chunk_length = u32::MAX;
+        assert_eq!(vpk.embed_chunk(), None);
+
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert_eq!(vpk.embed_chunk(), None);
+    }
+
+    #[test]
+    fn test_ext_from_path() {
+        use std::borrow::Cow;
+
+        assert_eq!(
+            Ext::from_path("materials/brick/a.vmt"),
+            (Ext::Vmt, "materials/brick/a")
+        );
+        assert_eq!(
+            Ext::from_path("models/gun.dx90.VTX"),
+            (Ext::Vtx, "models/gun.dx90")
+        );
+        assert_eq!(
+            Ext::from_path("scripts/items.txt"),
+            (Ext::Other(Cow::Borrowed(b"txt")), "scripts/items")
+        );
+        assert_eq!(
+            Ext::from_path("v1.2/readme"),
+            (Ext::Other(Cow::Borrowed(b" ")), "v1.2/readme")
+        );
+    }
+
+    #[test]
+    fn test_ext_from() {
+        use std::borrow::Cow;
+
+        assert_eq!(Ext::from("vmt"), Ext::Vmt);
+        assert_eq!(Ext::from(b"VTF".as_slice()), Ext::Vtf);
+        assert_eq!(Ext::from(b"MDL".to_vec()), Ext::Mdl);
+        assert!(matches!(Ext::from(b"mdl".to_vec()), Ext::Mdl));
+
+        let txt: Ext<'static> = Ext::from(String::from("TXT"));
+        assert_eq!(txt, Ext::Other(Cow::Borrowed(b"txt")));
+
+        let entries = [TestEntry::preload("vmt", "materials", "brick", b"abc")];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert!(vpk.get(&"vmt".into(), "materials", "brick").is_some());
+    }
+
+    #[test]
+    fn test_ext_other_known() {
+        use std::borrow::Cow;
+        use std::collections::HashSet;
+
+        let other = Ext::Other(Cow::Borrowed(b"vmt"));
+        assert_eq!(other, Ext::Vmt);
+        assert_eq!(other.cmp(&Ext::Vmt), std::cmp::Ordering::Equal);
+        assert!(Ext::Other(Cow::Borrowed(b"aaa")) > Ext::Mp3);
+        assert!(Ext::Vmt < Ext::Vtf);
+
+        let set: HashSet<_> = [Ext::Vmt, other.clone()].into_iter().collect();
+        assert_eq!(set.len(), 1);
+
+        // Case is ignored, the same as when looking up the map for the extension
+        let upper = Ext::Other(Cow::Borrowed(b"VMT"));
+        assert_eq!(upper, Ext::Vmt);
+        assert!(set.contains(&upper));
+        assert_eq!(upper.cmp(&Ext::Vmt), std::cmp::Ordering::Equal);
+        assert_eq!(
+            Ext::Other(Cow::Borrowed(b"Txt")),
+            Ext::Other(Cow::Borrowed(b"tXT"))
+        );
+        assert!(Ext::Other(Cow::Borrowed(b"B")) > Ext::Other(Cow::Borrowed(b"a")));
+
+        let entries = [TestEntry::preload("vmt", "materials", "brick", b"abc")];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert!(vpk.get(&other, "materials", "brick").is_some());
+        assert!(vpk.get(&upper, "materials", "brick").is_some());
+    }
+
+    #[test]
+    fn test_backslash_dirs() {
+        let entries = [
+            TestEntry::preload("mdl", "models\\props", "crate", b"abc"),
+            TestEntry::preload("mdl", "models\\props\\wood", "plank", b"def"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let handle = vpk.get(&Ext::Mdl, "models/props", "crate").unwrap();
+        assert_eq!(handle.get().unwrap().as_ref(), b"abc");
+        assert!(vpk
+            .get_ignore_case(&Ext::Mdl, "Models/Props/Wood", "plank")
+            .is_some());
+        assert_eq!(vpk.entries_by_dir("models/props").count(), 2);
+    }
+
+    #[test]
+    fn test_preload_out_of_bounds() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::preload("vmt", "materials", "b", b""),
+            TestEntry::preload("vtf", "materials", "c", b"ghi"),
+        ];
+        let mut data = build_vpk_v1(&entries, b"");
+        // Bump the preload length of `b` way past the end of the file
+        let name_pos = data.windows(2).position(|w| w == b"b\0").unwrap();
+        let preload_length_pos = name_pos + 2 + 4;
+        data[preload_length_pos..preload_length_pos + 2].copy_from_slice(&0xfff0u16.to_le_bytes());
+
+        let err = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None).unwrap_err();
+        match err {
+            Error::PreloadOutOfBounds { path, preload } => {
+                assert_eq!(path, "materials/b.vmt");
+                assert_eq!(preload.len(), 0xfff0);
+            }
+            err => panic!("unexpected error: {err:?}"),
+        }
+
+        let options = ReadOptions {
+            lenient: true,
+            ..ReadOptions::default()
+        };
+        let vpk = VPK::read_bytes_with_options(data, |i| i.to_string(), &options).unwrap();
+        assert_eq!(vpk.skipped_entries.len(), 1);
+        assert_eq!(vpk.skipped_entries[0].position, name_pos);
+        assert!(matches!(
+            *vpk.skipped_entries[0].error,
+            Error::PreloadOutOfBounds { .. }
+        ));
+        let a = vpk.get(&Ext::Vmt, "materials", "a").unwrap();
+        assert_eq!(a.get().unwrap().as_ref(), b"abc");
+        assert!(vpk.get(&Ext::Vmt, "materials", "b").is_none());
+        // The entries after it are still read
+        let c = vpk.get(&Ext::Vtf, "materials", "c").unwrap();
+        assert_eq!(c.get().unwrap().as_ref(), b"ghi");
+    }
+
+    #[test]
+    fn test_iter_sorted_by_archive() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "c", 1, 0, 1),
+            TestEntry::preload("vmt", "materials", "inline", b"abc"),
+            TestEntry::archive("vtf", "materials", "b", 0, 10, 1),
+            TestEntry::archive("mdl", "models", "a", 0, 0, 1),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let names: Vec<_> = vpk
+            .iter_sorted_by_archive()
+            .map(|(dir_file, _)| dir_file.filename())
+            .collect();
+        assert_eq!(names, [b"a".as_slice(), b"b", b"c", b"inline"]);
+    }
+
+    #[test]
+    fn test_entries_in_archive() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "a", 7, 0, 1),
+            TestEntry::preload("vmt", "materials", "inline", b"abc"),
+            TestEntry::archive("mdl", "models", "b", 7, 1, 1),
+            TestEntry::archive("vtf", "materials", "c", 0, 0, 1),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let names = |archive_index| {
+            let mut names: Vec<_> = vpk
+                .entries_in_archive(archive_index)
+                .map(|(ext, dir_file, _)| (ext.into_owned(), dir_file.filename().to_vec()))
+                .collect();
+            names.sort_by(|(_, a), (_, b)| a.cmp(b));
+            names
+        };
+        assert_eq!(
+            names(7),
+            [(Ext::Vtf, b"a".to_vec()), (Ext::Mdl, b"b".to_vec())]
+        );
+        assert_eq!(names(0), [(Ext::Vtf, b"c".to_vec())]);
+        assert_eq!(names(0x7fff), [(Ext::Vmt, b"inline".to_vec())]);
+        assert!(names(1).is_empty());
+    }
+
+    #[test]
+    fn test_read_cstring_out_of_bounds() {
+        let data = b"abc";
+        let mut reader = Cursor::new(&data[..]);
+        assert!(read_cstring(&mut reader).is_err());
+
+        reader.set_position(10);
+        assert!(read_cstring(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_read_truncated() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::archive("vtf", "materials", "b", 0, 0, 3),
+            TestEntry::preload("txt", "scripts", "c", b"defg"),
+        ];
+        let lenient = ReadOptions {
+            lenient: true,
+            ..ReadOptions::default()
+        };
+        let read = |data: &[u8], options: &ReadOptions| {
+            VPK::read_bytes_with_options(data, |i| i.to_string(), options)
+        };
+
+        // Version 1 is readable leniently as soon as the header is there, keeping the entries
+        // before the cut and skipping the rest
+        let data = build_vpk_v1(&entries, b"");
+        let mut last_count = 0;
+        for len in 0..data.len() {
+            let data = &data[..len];
+            assert!(read(data, &ReadOptions::default()).is_err());
+
+            let vpk = read(data, &lenient);
+            if len < 4 * 3 {
+                assert!(vpk.is_err());
+                continue;
+            }
+            let vpk = vpk.unwrap();
+            assert!(!vpk.skipped_entries.is_empty());
+            let count = vpk.iter().count();
+            assert!(count >= last_count);
+            last_count = count;
+            if let Some(a) = vpk.get(&Ext::Vmt, "materials", "a") {
+                assert_eq!(a.get().unwrap().as_ref(), b"abc");
+            }
+        }
+        assert_eq!(last_count, 3);
+
+        // Version 2 has its regions after the tree, which don't fit once it is cut off at all
+        let data = build_vpk_v2(&entries, b"embed");
+        for len in 0..data.len() {
+            let data = &data[..len];
+            assert!(read(data, &ReadOptions::default()).is_err());
+            assert!(read(data, &lenient).is_err());
+        }
+    }
+
+    #[test]
+    fn test_read_numbered_archive() {
+        let path = write_temp("read_numbered_archive", "pak01_001.vpk", b"not a header");
+        match VPK::read(&path, ProbableKind::None).unwrap_err() {
+            Error::NumberedArchive { path: p, suggested } => {
+                assert_eq!(p, path.to_str().unwrap());
+                assert_eq!(
+                    suggested,
+                    path.with_file_name("pak01_dir.vpk").to_str().unwrap()
+                );
+            }
+            err => panic!("unexpected error: {err:?}"),
+        }
+
+        let path = write_temp("read_numbered_archive", "map.bsp", b"not a header");
+        assert!(matches!(
+            VPK::read(&path, ProbableKind::None),
+            Err(Error::InvalidSignature)
+        ));
+        assert_eq!(numbered_archive_dir_path("pak01_dir.vpk"), None);
+        assert_eq!(numbered_archive_dir_path("001.vpk"), None);
+        assert_eq!(numbered_archive_dir_path("é123.vpk"), None);
+        assert_eq!(numbered_archive_dir_path("a_é1.vpk"), None);
+        assert_eq!(
+            numbered_archive_dir_path("é_001.vpk"),
+            Some("é_dir.vpk".to_string())
+        );
+    }
+
+    /// The same kind of mix of entries as a real misc VPK, for covering the full parse path
+    /// without needing a real VPK on disk like [`test_vpk_read`].
+    fn synthetic_entries() -> Vec<TestEntry> {
+        let mut entries = Vec::new();
+        for (i, name) in ["brick", "concrete", "wood", "metal"]
+            .into_iter()
+            .enumerate()
+        {
+            entries.push(TestEntry::preload(
+                "vmt",
+                "materials/world",
+                name,
+                b"\"LightmappedGeneric\"",
+            ));
+            entries.push(TestEntry::archive(
+                "vtf",
+                "materials/world",
+                name,
+                0,
+                i as u32 * 16,
+                16,
+            ));
+        }
+        for name in ["crate", "barrel"] {
+            entries.push(TestEntry::archive("mdl", "models/props", name, 1, 0, 8));
+            entries.push(TestEntry::archive("vvd", "models/props", name, 1, 8, 8));
+            entries.push(TestEntry::archive("phy", "models/props", name, 1, 16, 8));
+        }
+        entries.push(TestEntry::preload("txt", "scripts", "items_game", b"items"));
+        entries.push(TestEntry::preload(" ", " ", "readme", b"hi"));
+
+        entries
+    }
+
+    #[test]
+    fn test_read_synthetic_v2() {
+        let entries = synthetic_entries();
+        let data = build_vpk_v2(&entries, b"");
+        let vpk = VPK::read_bytes(
+            data,
+            |i| format!("pak01_{:03}.vpk", i),
+            ProbableKind::Tf2Misc,
+        )
+        .unwrap();
+
+        assert_eq!(vpk.header.version, 2);
+        assert!(vpk.verify_checksums().unwrap());
+        assert_eq!(vpk.entries().count(), entries.len());
+        assert_eq!(vpk.tree.vmt.len(), 4);
+        assert_eq!(vpk.tree.vtf.len(), 4);
+        assert_eq!(vpk.tree.mdl.len(), 2);
+        assert_eq!(vpk.tree.vvd.len(), 2);
+        assert_eq!(vpk.tree.phy.len(), 2);
+        assert_eq!(vpk.tree.other.len(), 2);
+        assert_eq!(
+            vpk.archive_paths,
+            [
+                Some(Arc::from("pak01_000.vpk")),
+                Some(Arc::from("pak01_001.vpk"))
+            ]
+        );
+
+        let wood = vpk.get(&Ext::Vmt, "materials/world", "wood").unwrap();
+        assert_eq!(wood.get().unwrap().as_ref(), b"\"LightmappedGeneric\"");
+        let barrel = vpk.get(&Ext::Phy, "models/props", "barrel").unwrap();
+        assert_eq!(barrel.archive_path(), Some("pak01_001.vpk"));
+        assert_eq!(barrel.archive_relative_path(), Some("pak01_001.vpk"));
+        assert_eq!(barrel.entry.dir_entry.archive_offset, 16);
+    }
+
+    #[test]
+    fn test_reload_into() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::preload("txt", "scripts", "b", b"def"),
+        ];
+        let (mut vpk, path) = read_with_archives("reload_into", &entries, &[]);
+        let capacity = vpk.tree.vmt.capacity();
+
+        let entries = [TestEntry::preload("vmt", "materials", "c", b"ghi")];
+        std::fs::write(&path, build_vpk_v1(&entries, b"")).unwrap();
+        vpk.reload_into(&path).unwrap();
+
+        assert_eq!(vpk.entries().count(), 1);
+        assert!(vpk.tree.vmt.capacity() >= capacity);
+        assert!(vpk.get(&Ext::Vmt, "materials", "a").is_none());
+        let c = vpk.get(&Ext::Vmt, "materials", "c").unwrap();
+        assert_eq!(c.get().unwrap().as_ref(), b"ghi");
+        assert_eq!(vpk.summary().ext_counts, [(Ext::Vmt, 1)]);
+
+        // The map for `txt` is gone, rather than left behind empty
+        assert_eq!(vpk.tree.other.len(), 0);
+
+        std::fs::write(&path, b"garbage").unwrap();
+        assert!(vpk.reload_into(&path).is_err());
+        // Nothing of the earlier read is left
+        assert_eq!(vpk.entries().count(), 0);
+        assert_eq!(vpk.dir_path(), None);
+        assert!(vpk.archive_paths.is_empty());
+        assert!(vpk.data.is_empty());
+    }
+
+    #[test]
+    fn test_reload_into_keeps_options() {
+        let entries = [TestEntry::archive("vtf", "materials", "brick", 0, 2, 3)];
+        let dir_path = write_temp(
+            "reload_into_options",
+            "pak01_dir.vpk",
+            &build_vpk_v1(&entries, b""),
+        );
+        let archive_dir = temp_dir("reload_into_options_archives");
+        std::fs::write(archive_dir.join("pak01_000.vpk"), b"..abc..").unwrap();
+
+        let options = ReadOptions {
+            archive_dir: Some(archive_dir.to_path_buf()),
+            normalize_case: true,
+            ..ReadOptions::default()
+        };
+        let mut vpk = VPK::read_with_options(&dir_path, &options).unwrap();
+        let archive_path = vpk.archive_path(0).map(str::to_string);
+        assert_eq!(
+            archive_path.as_deref(),
+            archive_dir.join("pak01_000.vpk").to_str()
+        );
+
+        vpk.reload_into(&dir_path).unwrap();
+        assert_eq!(vpk.archive_path(0), archive_path.as_deref());
+        assert!(vpk.normalized_case);
+        let brick = vpk.get(&Ext::Vtf, "materials", "brick").unwrap();
+        assert_eq!(brick.get().unwrap().as_ref(), b"abc");
+
+        // A failed reload keeps the options for the next one
+        std::fs::write(&*dir_path, b"garbage").unwrap();
+        assert!(vpk.reload_into(&dir_path).is_err());
+        std::fs::write(&*dir_path, build_vpk_v1(&entries, b"")).unwrap();
+        vpk.reload_into(&dir_path).unwrap();
+        assert_eq!(vpk.archive_path(0), archive_path.as_deref());
+    }
+
+    #[test]
+    fn test_normalize_case() {
+        let entries = [
+            TestEntry::preload("vmt", "Materials\\Brick", "Wall", b"abc"),
+            TestEntry::preload("vmt", "materials/brick", "floor", b"def"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let options = ReadOptions {
+            normalize_case: true,
+            ..ReadOptions::default()
+        };
+        let vpk = VPK::read_bytes_with_options(data, |i| i.to_string(), &options).unwrap();
+
+        let wall = vpk.get(&Ext::Vmt, "materials/brick", "wall").unwrap();
+        assert_eq!(wall.dir_file.dir(), b"materials/brick");
+        assert_eq!(wall.dir_file.filename(), b"wall");
+        // The data itself is left alone
+        assert_eq!(wall.get().unwrap().as_ref(), b"abc");
+        assert!(vpk.get(&Ext::Vmt, "materials", "brick/floor").is_some());
+        // Exact comparisons, so non-lowercase arguments don't match
+        assert!(vpk.get(&Ext::Vmt, "materials/brick", "Wall").is_none());
+        assert!(vpk
+            .get_ignore_case(&Ext::Vmt, "Materials/Brick", "Wall")
+            .is_some());
+    }
+
+    #[test]
+    fn test_find_overlaps() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "a", 0, 0, 10),
+            TestEntry::archive("vtf", "materials", "dup", 0, 0, 10),
+            TestEntry::archive("vtf", "materials", "partial", 0, 5, 10),
+            TestEntry::archive("vtf", "materials", "after", 0, 15, 5),
+            // Same range, but a different archive
+            TestEntry::archive("vtf", "materials", "other", 1, 0, 10),
+            TestEntry::archive("vtf", "materials", "empty", 0, 3, 0),
+            TestEntry::preload("vmt", "materials", "inline", b"abc"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let overlaps: Vec<_> = vpk
+            .find_overlaps()
+            .into_iter()
+            .map(|o| {
+                let mut names = [o.first.dir_file.filename(), o.second.dir_file.filename()];
+                names.sort();
+                (names, o.kind)
+            })
+            .collect();
+        assert_eq!(overlaps.len(), 3);
+        assert!(overlaps.contains(&([b"a".as_slice(), b"dup"], OverlapKind::Duplicate)));
+        assert!(overlaps.contains(&([b"a".as_slice(), b"partial"], OverlapKind::Partial)));
+        assert!(overlaps.contains(&([b"dup".as_slice(), b"partial"], OverlapKind::Partial)));
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let read = |entries: &[TestEntry]| {
+            VPK::read_bytes(
+                build_vpk_v1(entries, b""),
+                |i| i.to_string(),
+                ProbableKind::None,
+            )
+            .unwrap()
+        };
+        let b = |crc32, dir, name, archive_index, archive_offset| TestEntry {
+            crc32,
+            ..TestEntry::archive("vtf", dir, name, archive_index, archive_offset, 3)
+        };
+
+        let hash = read(&[
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            b(5, "materials", "b", 0, 0),
+        ])
+        .content_hash();
+        // Same files, in a different order, layout and casing
+        let moved = read(&[
+            b(5, "Materials", "B", 1, 10),
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+        ]);
+        assert_eq!(moved.content_hash(), hash);
+
+        let changed = read(&[
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            b(6, "materials", "b", 0, 0),
+        ]);
+        assert_ne!(changed.content_hash(), hash);
+        let fewer = read(&[TestEntry::preload("vmt", "materials", "a", b"abc")]);
+        assert_ne!(fewer.content_hash(), hash);
+    }
+
+    #[test]
+    fn test_read_start_offset() {
+        let entries = [TestEntry::preload("vmt", "materials", "a", b"abc")];
+        let mut data = b"CONTAINER".to_vec();
+        data.extend_from_slice(&build_vpk_v1(&entries, b""));
+        let options = ReadOptions {
+            start_offset: 9,
+            ..ReadOptions::default()
+        };
+
+        let vpk = VPK::read_bytes_with_options(data.clone(), |i| i.to_string(), &options).unwrap();
+        let a = vpk.get(&Ext::Vmt, "materials", "a").unwrap();
+        assert_eq!(a.get().unwrap().as_ref(), b"abc");
+
+        let err = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None);
+        assert!(matches!(err, Err(Error::InvalidSignature)));
+
+        let options = ReadOptions {
+            start_offset: data.len() + 1,
+            ..ReadOptions::default()
+        };
+        let err = VPK::read_bytes_with_options(data, |i| i.to_string(), &options);
+        assert!(matches!(err, Err(Error::RegionOutOfBounds(_))));
+    }
+
+    #[test]
+    fn test_iter_all() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::preload("vmt", "materials", "b", b"def"),
+            TestEntry::preload("txt", "scripts", "items", b"ghi"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+
+        let mut all: Vec<_> = vpk
+            .iter_all()
+            .map(|(ext, dir_file, _)| (ext.as_slice().to_vec(), dir_file.filename().to_vec()))
+            .collect();
+        all.sort();
+        assert_eq!(
+            all,
+            [
+                (b"txt".to_vec(), b"items".to_vec()),
+                (b"vmt".to_vec(), b"a".to_vec()),
+                (b"vmt".to_vec(), b"b".to_vec()),
+            ]
+        );
+        let (ext, _, _) = vpk
+            .iter_all()
+            .find(|(_, dir_file, _)| dir_file.filename() == b"items")
+            .unwrap();
+        assert!(matches!(ext, Ext::Other(_)));
+    }
+
+    #[test]
+    fn test_duplicate_entries() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"first"),
+            TestEntry::preload("vmt", "materials", "b", b"other"),
+        ];
+        // Put the same path in again under a new dir node, which is how a crafted VPK would do it.
+        // Without the ext and root terminators, so that the dir can be appended
+        let mut tree = build_tree(&entries);
+        tree.truncate(tree.len() - 2);
+        let second = build_tree(&[TestEntry::preload("vmt", "materials", "a", b"second")]);
+        // Without the ext
+        tree.extend_from_slice(&second[4..]);
+
+        let mut data = Vec::new();
+        VPKHeader {
+            signature: VPK_SIGNATURE,
+            version: 1,
+            tree_length: tree.len() as u32,
+        }
+        .write_le(&mut data)
+        .unwrap();
+        data.extend_from_slice(&tree);
+        let read = |on_duplicate, lenient| {
+            let options = ReadOptions {
+                on_duplicate,
+                lenient,
+                ..ReadOptions::default()
+            };
+            VPK::read_bytes_with_options(data.clone(), |i| i.to_string(), &options)
+        };
+
+        let vpk = read(OnDuplicate::Overwrite, false).unwrap();
+        let a = vpk.get(&Ext::Vmt, "materials", "a").unwrap();
+        assert_eq!(a.get().unwrap().as_ref(), b"second");
+        assert!(vpk.duplicate_entries.is_empty());
+
+        let vpk = read(OnDuplicate::Report, false).unwrap();
+        assert_eq!(vpk.duplicate_entries.len(), 1);
+        let dup = &vpk.duplicate_entries[0];
+        assert_eq!(dup.path, "materials/a.vmt");
+        assert_eq!(dup.replaced.dir_entry.preload_length, 5);
+        assert_eq!(&vpk.data[dup.replaced.preload_interval()], b"first");
+
+        let err = read(OnDuplicate::Error, false).unwrap_err();
+        assert!(matches!(err, Error::DuplicateEntry { path } if path == "materials/a.vmt"));
+
+        // The later entry is skipped, so the earlier one is kept
+        let vpk = read(OnDuplicate::Error, true).unwrap();
+        assert_eq!(vpk.skipped_entries.len(), 1);
+        assert_eq!(vpk.skipped_entries[0].path, "materials/a.vmt");
+        let a = vpk.get(&Ext::Vmt, "materials", "a").unwrap();
+        assert_eq!(a.get().unwrap().as_ref(), b"first");
+        assert!(vpk.get(&Ext::Vmt, "materials", "b").is_some());
+    }
+
+    #[test]
+    fn test_read_with_visitor() {
+        let entries = [
+            TestEntry::preload("mdl", "models/props", "barrel", b""),
+            TestEntry::preload("vvd", "models/props", "barrel", b""),
+            TestEntry::preload("vmt", "materials", "barrel", b"abc"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+
+        // Model name -> the extensions of its files
+        let models: Arc<Mutex<HashMap<String, Vec<String>>>> = Arc::default();
+        let visitor = {
+            let models = models.clone();
+            move |ext: &Ext<'_>, dir: &[u8], filename: &[u8], _: &VPKDirectoryEntry| {
+                if dir.starts_with(b"models/") {
+                    let name = format!(
+                        "{}/{}",
+                        String::from_utf8_lossy(dir),
+                        String::from_utf8_lossy(filename)
+                    );
+                    let ext = String::from_utf8_lossy(ext.as_slice()).into_owned();
+                    models.lock().unwrap().entry(name).or_default().push(ext);
+                }
+            }
+        };
+        let options = ReadOptions {
+            visitor: Some(Arc::new(visitor)),
+            ..ReadOptions::default()
+        };
+        let vpk = VPK::read_bytes_with_options(data, |i| i.to_string(), &options).unwrap();
+
+        assert_eq!(vpk.entries().count(), 3);
+        let models = models.lock().unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models["models/props/barrel"], ["mdl", "vvd"]);
+    }
+
+    #[test]
+    fn test_checksum_offset_out_of_bounds() {
+        let entries = [TestEntry::preload("vmt", "materials", "a", b"abc")];
+        let mut data = build_vpk_v2(&entries, b"");
+        // chunk_hashes_length, large enough that the offset would wrap a `u32`
+        data[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let err = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::LayoutOutOfBounds { region: "chunk hashes", end, len, .. }
+                if end > u64::from(u32::MAX) && len == data.len()
+        ));
+    }
+
+    #[test]
+    fn test_archive_paths_referenced_only() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "a", 2, 0, 1),
+            TestEntry::archive("vtf", "materials", "b", 0, 0, 1),
+            TestEntry::preload("vmt", "materials", "c", b"abc"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let vpk =
+            VPK::read_bytes(data, |i| format!("pak01_{:03}.vpk", i), ProbableKind::None).unwrap();
+        let paths: Vec<_> = vpk.archive_paths.iter().map(Option::as_deref).collect();
+        assert_eq!(paths, [Some("pak01_000.vpk"), None, Some("pak01_002.vpk")]);
+        assert_eq!(vpk.archive_path(1), None);
+        assert_eq!(vpk.archive_path(2), Some("pak01_002.vpk"));
+        assert_eq!(vpk.archive_path(3), None);
+        assert_eq!(vpk.stats().archive_count, 2);
+        let files = vpk.open_all_archive_paths();
+        // The archives don't exist, but the unreferenced one isn't opened at all
+        assert!(files.is_err());
+
+        let data = build_vpk_v1(&entries[2..], b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert!(vpk.archive_paths.is_empty());
+    }
+
+    #[test]
+    fn test_iter_paths() {
+        let entries = [
+            TestEntry::preload("vmt", "materials/brick", "a", b"abc"),
+            TestEntry::preload(" ", " ", "readme", b"hi"),
+            TestEntry::preload("txt", "scripts", "items", b""),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let expected = ["materials/brick/a.vmt", "readme", "scripts/items.txt"];
+
+        let vpk = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None).unwrap();
+        let mut paths: Vec<_> = vpk
+            .iter_paths()
+            .map(|(path, handle)| {
+                assert!(matches!(path, Cow::Owned(_)));
+                assert_eq!(path, handle.full_path());
+                path.into_owned()
+            })
+            .collect();
+        paths.sort();
+        assert_eq!(paths, expected);
+
+        let options = ReadOptions {
+            store_full_paths: true,
+            ..ReadOptions::default()
+        };
+        let vpk = VPK::read_bytes_with_options(data, |i| i.to_string(), &options).unwrap();
+        let mut paths: Vec<_> = vpk
+            .iter_paths()
+            .map(|(path, handle)| {
+                assert!(matches!(path, Cow::Borrowed(_)));
+                assert_eq!(path, handle.full_path());
+                path.into_owned()
+            })
+            .collect();
+        paths.sort();
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn test_inline_archive_offset() {
+        let entries = [TestEntry {
+            preload: b"pre",
+            ..TestEntry::archive("vtf", "materials", "a", 0x7fff, 2, 3)
+        }];
+        let tree_length = build_tree(&entries).len() as u32;
+
+        for (data, header_length) in [
+            (build_vpk_v1(&entries, b"..xyz"), 12),
+            (build_vpk_v2(&entries, b"..xyz"), 28),
+        ] {
+            let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+            let a = vpk.get(&Ext::Vtf, "materials", "a").unwrap();
+
+            // The offset is relative to the end of the tree, and made relative to the start of
+            // the file while reading
+            let dir_entry = &a.entry.dir_entry;
+            assert_eq!(vpk.header_length, header_length);
+            assert_eq!(dir_entry.archive_offset, header_length + tree_length + 2);
+            assert_eq!(a.get().unwrap().as_ref(), b"prexyz");
+            assert_eq!(a.preload_bytes(), b"pre");
+        }
+
+        // An offset that can't be made relative to the start of the file
+        let entries = [TestEntry::archive(
+            "vtf",
+            "materials",
+            "a",
+            0x7fff,
+            u32::MAX - 2,
+            0,
+        )];
+        let data = build_vpk_v1(&entries, b"");
+        let res = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None);
+        assert!(matches!(res, Err(Error::InlineDataOutOfBounds { .. })));
+    }
+
+    #[test]
+    fn test_archive_path_cache() {
+        let entries = [
+            TestEntry::archive("vtf", "materials", "a", 0, 0, 1),
+            TestEntry::archive("vtf", "materials", "b", 1, 0, 1),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let cache = ArchivePathCache::new();
+        let options = ReadOptions {
+            archive_path_cache: Some(cache.clone()),
+            ..ReadOptions::default()
+        };
+        let read = |options: &ReadOptions| {
+            VPK::read_bytes_with_options(data.clone(), |i| format!("pak01_{i:03}.vpk"), options)
+                .unwrap()
+        };
+
+        let a = read(&options);
+        let b = read(&options);
+        assert_eq!(cache.len(), 2);
+        for (a, b) in a.archive_paths.iter().zip(&b.archive_paths) {
+            assert!(Arc::ptr_eq(a.as_ref().unwrap(), b.as_ref().unwrap()));
+        }
+
+        let c = read(&ReadOptions::default());
+        assert_eq!(c.archive_paths, a.archive_paths);
+        assert!(!Arc::ptr_eq(
+            c.archive_paths[0].as_ref().unwrap(),
+            a.archive_paths[0].as_ref().unwrap()
+        ));
+
+        // Paths stay while any VPK uses them
+        drop(a);
+        cache.remove_unused();
+        assert_eq!(cache.len(), 2);
+        drop(b);
+        cache.remove_unused();
+        assert!(cache.is_empty());
+
+        // Unused paths are dropped as the cache grows, rather than kept forever
+        for i in 0..1000 {
+            let vpk = VPK::read_bytes_with_options(
+                data.clone(),
+                |archive| format!("vpk{i}_{archive:03}.vpk"),
+                &options,
+            )
+            .unwrap();
+            drop(vpk);
+        }
+        assert!(cache.len() <= 64, "{}", cache.len());
+    }
+
+    #[test]
+    fn test_keep_v2_regions() {
+        let entries = [TestEntry::preload("vmt", "materials", "a", b"abc")];
+        let data = build_vpk_v2(&entries, b"");
+        let options = ReadOptions {
+            keep_v2_regions: true,
+            ..ReadOptions::default()
+        };
+
+        let vpk = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None).unwrap();
+        assert!(vpk.v2_regions.is_none());
+
+        let vpk = VPK::read_bytes_with_options(data.clone(), |i| i.to_string(), &options).unwrap();
+        let regions = vpk.v2_regions.clone().unwrap();
+        assert!(regions.chunk_hashes.is_empty());
+        assert!(regions.signature.is_empty());
+        assert_eq!(regions.self_hashes.len(), 48);
+        let checksum =
+            VPKHeaderV2Checksum::read_le(&mut Cursor::new(&regions.self_hashes)).unwrap();
+        assert_eq!(Some(&checksum), vpk.header_v2_checksum.as_ref());
+        drop(vpk);
+        assert_eq!(&data[data.len() - 48..], regions.self_hashes);
+
+        let v1 = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes_with_options(v1, |i| i.to_string(), &options).unwrap();
+        assert!(vpk.v2_regions.is_none());
+    }
+
+    #[test]
+    fn test_self_hashes_length() {
+        let entries = [TestEntry::preload("vmt", "materials", "a", b"abc")];
+        let lenient = ReadOptions {
+            lenient: true,
+            ..ReadOptions::default()
+        };
+        let read = |data: &[u8], options: &ReadOptions| {
+            VPK::read_bytes_with_options(data, |i| i.to_string(), options)
+        };
+        // The self hashes length is the sixth `u32` of the header
+        let with_self_hashes = |length: u32, extra: &[u8]| {
+            let mut data = build_vpk_v2(&entries, b"");
+            data[20..24].copy_from_slice(&length.to_le_bytes());
+            data.extend_from_slice(extra);
+            data
+        };
+
+        // 16 extra bytes after the usual checksums
+        let data = with_self_hashes(64, &[0; 16]);
+        assert!(matches!(
+            read(&data, &ReadOptions::default()),
+            Err(Error::HashSizeMismatch)
+        ));
+        let vpk = read(&data, &lenient).unwrap();
+        assert!(vpk.header_v2_checksum.is_some());
+        assert_eq!(vpk.v2_layout().unwrap().self_hashes.len(), 64);
+        assert!(vpk.verify_tree_checksum().unwrap());
+        assert!(vpk.get(&Ext::Vmt, "materials", "a").is_some());
+
+        // Too short to hold the checksums, so there are none
+        let mut data = with_self_hashes(16, &[]);
+        data.truncate(data.len() - 32);
+        let vpk = read(&data, &lenient).unwrap();
+        assert!(vpk.header_v2_checksum.is_none());
+        assert!(matches!(
+            vpk.verify_tree_checksum(),
+            Err(Error::NoChecksums)
+        ));
+
+        // Claims more than the file has
+        let data = with_self_hashes(64, &[]);
+        assert!(matches!(
+            read(&data, &lenient),
+            Err(Error::LayoutOutOfBounds {
+                region: "self hashes",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_stats() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::preload("vmt", "materials", "b", b"de"),
+            TestEntry::archive("vtf", "materials", "a", 0, 0, 10),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+
+        let vpk = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None).unwrap();
+        assert!(vpk.parse_stats().is_none());
+
+        let options = ReadOptions {
+            record_stats: true,
+            ..ReadOptions::default()
+        };
+        let vpk = VPK::read_bytes_with_options(data, |i| i.to_string(), &options).unwrap();
+        assert_eq!(vpk.entries().count(), 3);
+        let stats = vpk.parse_stats().unwrap();
+        assert_eq!(stats.per_ext_counts, [(Ext::Vmt, 2), (Ext::Vtf, 1)]);
+        assert_eq!(stats.total_bytes, 15);
+    }
+
+    #[test]
+    fn test_signature_region() {
+        let entries = [TestEntry::preload("vmt", "materials", "a", b"abc")];
+        let data = build_vpk_v2(&entries, b"");
+        let vpk = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None).unwrap();
+        assert_eq!(vpk.signature_region(), None);
+
+        let mut region = Vec::new();
+        region.extend_from_slice(&3u32.to_le_bytes());
+        region.extend_from_slice(b"key");
+        region.extend_from_slice(&4u32.to_le_bytes());
+        region.extend_from_slice(b"sign");
+        let mut signed = data;
+        // signature_length
+        signed[24..28].copy_from_slice(&(region.len() as u32).to_le_bytes());
+        signed.extend_from_slice(&region);
+
+        let vpk = VPK::read_bytes(signed, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert_eq!(vpk.signature_region(), Some(region.as_slice()));
+
+        let data = build_vpk_v1(&entries, b"");
+        let vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert_eq!(vpk.signature_region(), None);
+    }
+
+    #[test]
+    fn test_read_hardened() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "Brick", b"abc"),
+            TestEntry::preload("txt", "scripts", "items", b"def"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let options = ReadOptions::default().with_hasher(SipRandomState::new());
+        let vpk: VPK<SipRandomState> =
+            VPK::read_bytes_with_options(data.clone(), |i| i.to_string(), &options).unwrap();
+
+        let txt = Ext::from_ext_slice(b"txt");
+
+        let brick = vpk.get(&Ext::Vmt, "materials", "brick").unwrap();
+        assert_eq!(brick.get().unwrap().as_ref(), b"abc");
+        let items = vpk.get_ignore_case(&txt, "Scripts", "Items").unwrap();
+        assert_eq!(items.get().unwrap().as_ref(), b"def");
+        let flat = vpk.flat_tree();
+        assert!(flat.get(&Ext::Vmt, "materials", "brick").is_some());
+
+        // Any hasher can be used through the options
+        let options =
+            ReadOptions::new(ProbableKind::None).with_hasher(std::hash::BuildHasherDefault::<
+                std::collections::hash_map::DefaultHasher,
+            >::default());
+        let vpk = VPK::read_bytes_with_options(data, |i| i.to_string(), &options).unwrap();
+        let items = vpk.get_ignore_case(&txt, "Scripts", "Items").unwrap();
+        assert_eq!(items.get().unwrap().as_ref(), b"def");
+    }
+
+    #[test]
+    fn test_reparse_from() {
+        let entries = [
+            TestEntry::preload("vmt", "Materials", "Brick", b"abc"),
+            TestEntry::archive("vtf", "materials", "wall", 1, 0, 3),
+        ];
+        let (vpk, path) = read_with_archives("reparse_from", &entries, &[]);
+
+        let options = ReadOptions {
+            normalize_case: true,
+            ..ReadOptions::default()
+        };
+        let normalized = VPK::reparse_from(&vpk, &options).unwrap();
+        assert!(Arc::ptr_eq(&vpk.data, &normalized.data));
+        assert_eq!(normalized.archive_paths, vpk.archive_paths);
+        assert_eq!(normalized.dir_path(), Some(&*path));
+        assert!(normalized.source_modified().is_ok());
+
+        let brick = normalized.get(&Ext::Vmt, "materials", "brick").unwrap();
+        assert_eq!(brick.dir_file.dir(), b"materials");
+        assert_eq!(brick.get().unwrap().as_ref(), b"abc");
+        let brick = vpk.get(&Ext::Vmt, "materials", "brick").unwrap();
+        assert_eq!(brick.dir_file.dir(), b"Materials");
+
+        let wall = normalized.get(&Ext::Vtf, "materials", "wall").unwrap();
+        assert_eq!(
+            wall.archive_path(),
+            vpk.get(&Ext::Vtf, "materials", "wall")
+                .unwrap()
+                .archive_path()
+        );
+    }
+
+    #[test]
+    fn test_ext_bytes_fmt() {
+        let ext = ExtBytes::from(b"txt".as_slice());
+        assert_eq!(ext.to_string(), "txt");
+        assert_eq!(format!("{ext:?}"), "\"txt\"");
+
+        let ext = ExtBytes(b"a\xff\n\"".to_vec());
+        assert_eq!(ext.to_string(), "a\\xff\\n\\\"");
+        assert_eq!(format!("{ext:?}"), "\"a\\xff\\n\\\"\"");
+    }
+
+    #[test]
+    fn test_misplaced_other_exts() {
+        let entries = [
+            TestEntry::preload("VMT", "materials", "a", b""),
+            TestEntry::preload("Txt", "scripts", "b", b""),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+        let mut vpk = VPK::read_bytes(data, |i| i.to_string(), ProbableKind::None).unwrap();
+        assert_eq!(vpk.tree.vmt.len(), 1);
+        let other: Vec<_> = vpk.tree.other.keys().map(ExtBytes::as_slice).collect();
+        assert_eq!(other, [b"txt"]);
+        assert_eq!(vpk.tree.misplaced_other_exts().count(), 0);
+
+        let txt = vpk.tree.other[b"txt".as_slice()].clone();
+        vpk.tree.other.insert(b"vmt".to_vec().into(), txt.clone());
+        vpk.tree.other.insert(b"Cfg".to_vec().into(), txt);
+        let misplaced: Vec<_> = vpk.tree.misplaced_other_exts().collect();
+        assert_eq!(misplaced, [b"vmt".as_slice(), b"Cfg"]);
+    }
+
+    #[test]
+    fn test_trim_paths() {
+        let entries = [
+            TestEntry::preload("vmt", "materials\r", "a", b"abc"),
+            TestEntry::preload("vmt", "materials", "b \r\n", b"def"),
+            TestEntry::preload("vmt", " ", "c", b"ghi"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+
+        let vpk = VPK::read_bytes(data.clone(), |i| i.to_string(), ProbableKind::None).unwrap();
+        assert!(vpk.get(&Ext::Vmt, "materials", "a").is_none());
+        assert!(vpk.get(&Ext::Vmt, "materials", "b").is_none());
+
+        let options = ReadOptions {
+            trim_paths: true,
+            ..ReadOptions::default()
+        };
+        let vpk = VPK::read_bytes_with_options(data, |i| i.to_string(), &options).unwrap();
+        let a = vpk.get(&Ext::Vmt, "materials", "a").unwrap();
+        assert_eq!(a.dir_file.dir(), b"materials");
+        assert_eq!(a.get().unwrap().as_ref(), b"abc");
+        let b = vpk.get(&Ext::Vmt, "materials", "b").unwrap();
+        assert_eq!(b.dir_file.filename(), b"b");
+        assert_eq!(b.get().unwrap().as_ref(), b"def");
+        let c = vpk.get(&Ext::Vmt, " ", "c").unwrap();
+        assert_eq!(c.dir_file.dir(), b" ");
+        assert_eq!(c.get().unwrap().as_ref(), b"ghi");
+    }
+
+    #[test]
+    fn test_ext_filter() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b"abc"),
+            TestEntry::archive("mdl", "models", "b", 0, 0, 3),
+            TestEntry::preload("txt", "scripts", "c", b"def"),
+            TestEntry::archive("vtx", "models", "b.dx90", 1, 0, 3),
+            TestEntry::preload("phy", "models", "b", b"ghi"),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+
+        let options = ReadOptions {
+            ext_filter: Some([Ext::Mdl, Ext::Other(Cow::Borrowed(b"phy"))].into()),
+            ..ReadOptions::default()
+        };
+        let vpk = VPK::read_bytes_with_options(data, |i| i.to_string(), &options).unwrap();
+        assert_eq!(vpk.iter().count(), 2);
+        assert!(vpk.get(&Ext::Mdl, "models", "b").is_some());
+        let phy = vpk.get(&Ext::Phy, "models", "b").unwrap();
+        assert_eq!(phy.get().unwrap().as_ref(), b"ghi");
+        assert!(vpk.get(&Ext::Vmt, "materials", "a").is_none());
+        // Skipped entries don't need archive paths
+        assert_eq!(vpk.archive_paths.len(), 1);
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let entries = [
+            TestEntry::preload("vmt", "materials", "a", b""),
+            TestEntry::preload("vmt", "materials", "b", b""),
+            TestEntry::preload("vtf", "materials", "c", b""),
+        ];
+        let data = build_vpk_v1(&entries, b"");
+
+        let options = |max_entries| ReadOptions {
+            max_entries,
+            ..ReadOptions::default()
+        };
+        let read = |options: &ReadOptions| {
+            VPK::read_bytes_with_options(data.clone(), |i| i.to_string(), options)
+        };
+        assert_eq!(read(&options(None)).unwrap().iter().count(), 3);
+        assert_eq!(read(&options(Some(3))).unwrap().iter().count(), 3);
+        assert!(matches!(
+            read(&options(Some(2))),
+            Err(Error::TooManyEntries { max: 2 })
+        ));
+
+        // Lenient reading keeps the entries before the limit
+        let vpk = read(&ReadOptions {
+            lenient: true,
+            ..options(Some(2))
+        })
+        .unwrap();
+        assert_eq!(vpk.iter().count(), 2);
+        assert!(matches!(
+            *vpk.skipped_entries[0].error,
+            Error::TooManyEntries { .. }
+        ));
     }
 
     #[test]
@@ -704,7 +5009,7 @@ mod tests {
             // other entry counts per ext
             println!("other: {}", res.tree.other.len());
             for (ext, map) in &res.tree.other {
-                println!("\t {}: {}", String::from_utf8_lossy(ext), map.len());
+                println!("\t {}: {}", ext, map.len());
             }
 
             panic!();